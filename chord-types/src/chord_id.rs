@@ -0,0 +1,338 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// The identifier of nodes and keys on the ring.
+///
+/// To change the size of the ring, change the number of bytes here, e.g. to `Id<20>` for 160 bits.
+pub type ChordId = Id<16>;
+
+/// A position on a ring of size 2^BITS, where BITS is `8 * BYTES`.
+///
+/// The bytes are stored in big-endian order, so the derived ordering is the numeric one.
+/// Arithmetic is performed modulo 2^BITS.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Id<const BYTES: usize>([u8; BYTES]);
+
+impl<const BYTES: usize> Id<BYTES> {
+    /// The number of bits of an id, which is m in the Chord paper.
+    pub const BITS: usize = BYTES * 8;
+    pub const ZERO: Self = Self([0; BYTES]);
+    pub const MAX: Self = Self([u8::MAX; BYTES]);
+
+    pub const fn from_be_bytes(bytes: [u8; BYTES]) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn to_be_bytes(self) -> [u8; BYTES] {
+        self.0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Uses the leading bytes of a hash digest as id.
+    ///
+    /// If the digest is shorter than the id, the remaining bytes are zero.
+    pub fn from_digest(digest: &[u8]) -> Self {
+        let length = BYTES.min(digest.len());
+        let mut bytes = [0; BYTES];
+        bytes[..length].copy_from_slice(&digest[..length]);
+        Self(bytes)
+    }
+
+    /// Returns 2^`exponent`, which is the offset of the finger at index `exponent`.
+    ///
+    /// Panics if `exponent` is not smaller than [Self::BITS].
+    pub fn pow2(exponent: usize) -> Self {
+        assert!(
+            exponent < Self::BITS,
+            "2^{} does not fit into {} bits",
+            exponent,
+            Self::BITS
+        );
+        let mut bytes = [0; BYTES];
+        bytes[BYTES - 1 - exponent / 8] = 1 << (exponent % 8);
+        Self(bytes)
+    }
+
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        let mut bytes = [0; BYTES];
+        let mut carry = 0;
+        for i in (0..BYTES).rev() {
+            let sum = self.0[i] as u16 + rhs.0[i] as u16 + carry;
+            bytes[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        Self(bytes)
+    }
+
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        let mut bytes = [0; BYTES];
+        let mut borrow = 0;
+        for i in (0..BYTES).rev() {
+            let difference = self.0[i] as i16 - rhs.0[i] as i16 - borrow;
+            bytes[i] = difference as u8;
+            borrow = (difference < 0) as i16;
+        }
+        Self(bytes)
+    }
+
+    /// Returns how far `other` is from this id, going clockwise around the ring.
+    pub fn distance_to(self, other: Self) -> Self {
+        other.wrapping_sub(self)
+    }
+
+    /// Returns the id as a fraction of a full turn around the ring, in `[0, 1)`.
+    ///
+    /// Only the leading 53 bits are exact.
+    pub fn as_fraction(self) -> f64 {
+        self.0
+            .iter()
+            .rev()
+            .fold(0.0, |fraction, &byte| (fraction + byte as f64) / 256.0)
+    }
+
+    /// Returns the id at the given fraction of a full turn around the ring, rounding down.
+    ///
+    /// Fractions outside of `[0, 1)` are clamped to [Self::ZERO] and [Self::MAX].
+    pub fn from_fraction(fraction: f64) -> Self {
+        if fraction.is_nan() || fraction <= 0.0 {
+            return Self::ZERO;
+        }
+        let mut bytes = [0; BYTES];
+        let mut rest = fraction.min(1.0);
+        for byte in &mut bytes {
+            rest *= 256.0;
+            let digit = rest.floor().min(255.0);
+            *byte = digit as u8;
+            rest -= digit;
+        }
+        Self(bytes)
+    }
+}
+
+impl<const BYTES: usize> Default for Id<BYTES> {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl<const BYTES: usize> Add for Id<BYTES> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        self.wrapping_add(rhs)
+    }
+}
+
+impl<const BYTES: usize> Sub for Id<BYTES> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.wrapping_sub(rhs)
+    }
+}
+
+impl<const BYTES: usize> From<u128> for Id<BYTES> {
+    /// Converts `value` into an id, reducing it modulo 2^BITS.
+    fn from(value: u128) -> Self {
+        let value = value.to_be_bytes();
+        let length = BYTES.min(value.len());
+        let mut bytes = [0; BYTES];
+        bytes[BYTES - length..].copy_from_slice(&value[value.len() - length..]);
+        Self(bytes)
+    }
+}
+
+impl<const BYTES: usize> From<u64> for Id<BYTES> {
+    /// Converts `value` into an id, reducing it modulo 2^BITS.
+    fn from(value: u64) -> Self {
+        Self::from(value as u128)
+    }
+}
+
+impl<const BYTES: usize> TryFrom<&[u8]> for Id<BYTES> {
+    type Error = ParseIdError;
+
+    /// Reads an id from exactly `BYTES` big-endian bytes.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(bytes.try_into().map_err(|_| {
+            ParseIdError::invalid_length(bytes.len(), BYTES)
+        })?))
+    }
+}
+
+impl<const BYTES: usize> Display for Id<BYTES> {
+    /// Formats the id as hexadecimal number with `2 * BYTES` digits.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl<const BYTES: usize> Debug for Id<BYTES> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<const BYTES: usize> FromStr for Id<BYTES> {
+    type Err = ParseIdError;
+
+    /// Parses a hexadecimal number of at most `2 * BYTES` digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > 2 * BYTES {
+            return Err(ParseIdError::invalid_length(s.len(), 2 * BYTES));
+        }
+        if !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ParseIdError::invalid_digit(s));
+        }
+        let padded = format!("{:0>width$}", s, width = 2 * BYTES);
+        let mut bytes = [0; BYTES];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&padded[2 * i..2 * i + 2], 16)
+                .map_err(|_| ParseIdError::invalid_digit(s))?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// Ids are serialized as hexadecimal strings, like they are displayed, in human-readable formats,
+/// and as a fixed-size tuple of big-endian bytes otherwise.
+#[cfg(feature = "serde")]
+impl<const BYTES: usize> serde::Serialize for Id<BYTES> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple;
+
+        if serializer.is_human_readable() {
+            return serializer.collect_str(self);
+        }
+        let mut tuple = serializer.serialize_tuple(BYTES)?;
+        for byte in &self.0 {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const BYTES: usize> serde::Deserialize<'de> for Id<BYTES> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let id = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+            return id.parse().map_err(serde::de::Error::custom);
+        }
+        deserializer.deserialize_tuple(BYTES, IdBytesVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct IdBytesVisitor<const BYTES: usize>;
+
+#[cfg(feature = "serde")]
+impl<'de, const BYTES: usize> serde::de::Visitor<'de> for IdBytesVisitor<BYTES> {
+    type Value = Id<BYTES>;
+
+    fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{} id bytes", BYTES)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0; BYTES];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(index, &self))?;
+        }
+        Ok(Id(bytes))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum ParseIdError {
+    #[error("invalid id length {length}, expected at most {expected}")]
+    InvalidLength { length: usize, expected: usize },
+    #[error("invalid hexadecimal id: {0}")]
+    InvalidDigit(String),
+}
+
+impl ParseIdError {
+    pub fn invalid_length(length: usize, expected: usize) -> Self {
+        ParseIdError::InvalidLength { length, expected }
+    }
+
+    pub fn invalid_digit(id: impl Into<String>) -> Self {
+        ParseIdError::InvalidDigit(id.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type SmallId = Id<2>;
+
+    #[test]
+    fn test_arithmetic_wraps_around() {
+        assert_eq!(SmallId::MAX + SmallId::from(2u64), SmallId::from(1u64));
+        assert_eq!(SmallId::ZERO - SmallId::from(1u64), SmallId::MAX);
+        assert_eq!(
+            SmallId::from(0x1ffu64) + SmallId::from(1u64),
+            SmallId::from(0x200u64)
+        );
+        assert_eq!(
+            SmallId::from(0x200u64) - SmallId::from(1u64),
+            SmallId::from(0x1ffu64)
+        );
+        assert_eq!(SmallId::from(0x12345u64), SmallId::from(0x2345u64));
+        assert_eq!(
+            SmallId::from(10u64).distance_to(SmallId::from(5u64)),
+            SmallId::from(0xfffbu64)
+        );
+        for exponent in 0..SmallId::BITS {
+            assert_eq!(SmallId::pow2(exponent), SmallId::from(1u64 << exponent));
+        }
+        assert_eq!(ChordId::pow2(127).to_be_bytes()[0], 0x80);
+    }
+
+    #[test]
+    fn test_fractions() {
+        assert_eq!(SmallId::ZERO.as_fraction(), 0.0);
+        assert_eq!(SmallId::from(0x8000u64).as_fraction(), 0.5);
+        assert_eq!(SmallId::from(0x4001u64).as_fraction(), 0.25 + 1.0 / 65536.0);
+        assert_eq!(SmallId::from_fraction(0.25), SmallId::from(0x4000u64));
+        assert_eq!(SmallId::from_fraction(-1.0), SmallId::ZERO);
+        assert_eq!(SmallId::from_fraction(1.0), SmallId::MAX);
+        let id = ChordId::from(0x0001_2345_6789_abcd_u128 << 76);
+        assert_eq!(ChordId::from_fraction(id.as_fraction()), id);
+    }
+
+    #[test]
+    fn test_encodings() {
+        let id = ChordId::from(0xabcdef_u64);
+        assert_eq!(id.to_string(), "00000000000000000000000000abcdef");
+        assert_eq!("abcdef".parse::<ChordId>(), Ok(id));
+        assert_eq!(id.to_string().parse::<ChordId>(), Ok(id));
+        assert_eq!(ChordId::try_from(id.as_bytes()), Ok(id));
+        assert!("".parse::<ChordId>().is_err());
+        assert!("xyz".parse::<ChordId>().is_err());
+        assert!("+1".parse::<ChordId>().is_err());
+        assert!(format!("1{}", ChordId::MAX).parse::<ChordId>().is_err());
+        assert!(ChordId::try_from(&[0u8; 3][..]).is_err());
+        assert!(ChordId::from(1u64) < ChordId::from(256u64));
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+//! A compact binary encoding for the serializable types of this crate, meant for persisting
+//! routing state and exchanging it outside of protobuf.
+//!
+//! The payload is prefixed with a format version. The bincode options are pinned explicitly, so
+//! that changes of the library defaults do not silently change the format.
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+/// The version written in front of every encoded value. Bump it when the layout changes.
+pub const FORMAT_VERSION: u8 = 1;
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_little_endian()
+        .reject_trailing_bytes()
+}
+
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = vec![FORMAT_VERSION];
+    options().serialize_into(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, EncodingError> {
+    match bytes.split_first() {
+        Some((&FORMAT_VERSION, payload)) => Ok(options().deserialize(payload)?),
+        Some((&version, _)) => Err(EncodingError::unsupported_version(version)),
+        None => Err(EncodingError::Empty),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EncodingError {
+    #[error("cannot decode an empty buffer")]
+    Empty,
+    #[error("unsupported format version {0}, expected {FORMAT_VERSION}")]
+    UnsupportedVersion(u8),
+    #[error("bincode error: {0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+impl EncodingError {
+    pub fn unsupported_version(version: u8) -> Self {
+        EncodingError::UnsupportedVersion(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use crate::chord_id::ChordId;
+    use crate::finger_table::{FingerTable, FingerTableEntry};
+    use crate::node_info::NodeInfo;
+
+    use super::*;
+
+    fn node(id: u128) -> NodeInfo {
+        NodeInfo::with_address(
+            ChordId::from(id),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 5000)),
+        )
+    }
+
+    #[test]
+    fn test_node_info_layout_is_stable() {
+        let mut expected = vec![FORMAT_VERSION];
+        expected.extend(ChordId::from(1u128).to_be_bytes());
+        // one endpoint: http, ip host, IPv4 variant, address, varint port
+        expected.extend([1, 0, 0, 0, 127, 0, 0, 1, 251, 0x88, 0x13]);
+        assert_eq!(encode(&node(1)).unwrap(), expected);
+        assert_eq!(decode::<NodeInfo>(&expected).unwrap(), node(1));
+    }
+
+    #[test]
+    fn test_finger_table_round_trip() {
+        let mut table = FingerTable::new(ChordId::from(100u128));
+        table
+            .get_successors_mut()
+            .push(FingerTableEntry::new(node(110)));
+        table
+            .get_predecessors_mut()
+            .push(FingerTableEntry::new(node(90)));
+        table.update_with(node(200));
+
+        let bytes = encode(&table).unwrap();
+        assert_eq!(decode::<FingerTable>(&bytes).unwrap(), table);
+
+        assert!(matches!(
+            decode::<FingerTable>(&[]),
+            Err(EncodingError::Empty)
+        ));
+        let mut unsupported = bytes.clone();
+        unsupported[0] = FORMAT_VERSION + 1;
+        assert!(matches!(
+            decode::<FingerTable>(&unsupported),
+            Err(EncodingError::UnsupportedVersion(_))
+        ));
+        assert!(decode::<FingerTable>(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
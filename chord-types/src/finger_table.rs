@@ -6,10 +6,22 @@
  * https://opensource.org/licenses/MIT.
  */
 
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::chord_id::ChordId;
 use crate::node_info::NodeInfo;
+use crate::ring_interval::RingInterval;
 
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FingerTable {
+    /// The id of the node that owns this table. Finger starts are relative to it.
+    id: ChordId,
+
     /// The predecessors of the node.
     /// Usually, one is enough, but for stability, multiple can be used.
     predecessors: Vec<FingerTableEntry>,
@@ -20,24 +32,62 @@ pub struct FingerTable {
 
     /// The entries of the finger table.
     ///
-    /// The size is ensured to be m (128), but entries can be empty.
+    /// The size is ensured to be m ([Id::BITS](crate::chord_id::Id::BITS)), but entries can be
+    /// empty.
     entries: Vec<Option<FingerTableEntry>>,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FingerTableEntry {
     pub node_info: NodeInfo,
+
+    /// When this entry was last confirmed to be correct.
+    pub updated_at: SystemTime,
+}
+
+impl FingerTableEntry {
+    pub fn new(node_info: NodeInfo) -> Self {
+        Self {
+            node_info,
+            updated_at: SystemTime::now(),
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        self.updated_at.elapsed().unwrap_or_default()
+    }
+}
+
+/// Describes how complete and up-to-date the entries of a [FingerTable] are.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct FingerTableStats {
+    pub size: usize,
+    pub populated: usize,
+    /// The age of the least recently updated entry.
+    pub max_age: Option<Duration>,
+    pub mean_age: Option<Duration>,
 }
 
 impl FingerTable {
-    pub fn new(size: usize) -> Self {
+    pub fn new(id: ChordId) -> Self {
         Self {
+            id,
             predecessors: Vec::new(),
             successors: Vec::new(),
-            entries: vec![None; size],
+            entries: vec![None; ChordId::BITS],
         }
     }
 
+    pub fn id(&self) -> ChordId {
+        self.id
+    }
+
+    /// The first id that finger `index` is responsible for, i.e. `id + 2^index`.
+    pub fn finger_start(&self, index: usize) -> ChordId {
+        self.id + ChordId::pow2(index)
+    }
+
     pub fn get_predecessors(&self) -> &Vec<FingerTableEntry> {
         &self.predecessors
     }
@@ -61,4 +111,180 @@ impl FingerTable {
     pub fn get_entries_mut(&mut self) -> &mut [Option<FingerTableEntry>] {
         &mut self.entries
     }
+
+    pub fn get_stats(&self) -> FingerTableStats {
+        let ages: Vec<_> = self.entries.iter().flatten().map(|e| e.age()).collect();
+        FingerTableStats {
+            size: self.entries.len(),
+            populated: ages.len(),
+            max_age: ages.iter().max().cloned(),
+            mean_age: (!ages.is_empty()).then(|| ages.iter().sum::<Duration>() / ages.len() as u32),
+        }
+    }
+
+    /// Iterates over every node known to this table exactly once, in the order successors,
+    /// predecessors, fingers.
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeInfo> {
+        let mut seen = HashSet::new();
+        self.successors
+            .iter()
+            .chain(&self.predecessors)
+            .chain(self.entries.iter().flatten())
+            .map(|entry| &entry.node_info)
+            .filter(move |node| seen.insert(node.id))
+    }
+
+    /// All known nodes that lie strictly between the owner and `id`, ordered by how closely they
+    /// precede `id`.
+    ///
+    /// The first node is the best next hop for a lookup of `id`; the rest are fallbacks in case it
+    /// turns out to be dead.
+    pub fn preceding_nodes(&self, id: ChordId) -> Vec<NodeInfo> {
+        let mut nodes: Vec<_> = self
+            .nodes()
+            .filter(|node| RingInterval::open(self.id, id).contains(node.id))
+            .cloned()
+            .collect();
+        nodes.sort_by_key(|node| node.id.distance_to(id));
+        nodes
+    }
+
+    /// The known node that most closely precedes `id`, if any.
+    pub fn closest_preceding(&self, id: ChordId) -> Option<NodeInfo> {
+        self.nodes()
+            .filter(|node| RingInterval::open(self.id, id).contains(node.id))
+            .min_by_key(|node| node.id.distance_to(id))
+            .cloned()
+    }
+
+    /// Slots `node` into every finger for which it is a better successor of the finger start than
+    /// the current entry. Empty fingers always take the node.
+    ///
+    /// Returns whether any finger changed.
+    pub fn update_with(&mut self, node: NodeInfo) -> bool {
+        if node.id == self.id {
+            return false;
+        }
+        let mut changed = false;
+        for index in 0..self.entries.len() {
+            let start = self.finger_start(index);
+            let entry = &mut self.entries[index];
+            let improves = match entry {
+                Some(entry) if entry.node_info == node => {
+                    entry.updated_at = SystemTime::now();
+                    false
+                }
+                Some(entry) => start.distance_to(node.id) < start.distance_to(entry.node_info.id),
+                None => true,
+            };
+            if improves {
+                *entry = Some(FingerTableEntry::new(node.clone()));
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Removes the node with the given id from the predecessor list, the successor list and all
+    /// fingers, e.g. because it was found to be dead or left the ring.
+    ///
+    /// Returns whether the node was known.
+    pub fn remove_node(&mut self, id: ChordId) -> bool {
+        let known = self.predecessors.len() + self.successors.len();
+        self.predecessors.retain(|entry| entry.node_info.id != id);
+        self.successors.retain(|entry| entry.node_info.id != id);
+        let mut removed = known != self.predecessors.len() + self.successors.len();
+        for entry in &mut self.entries {
+            if entry.as_ref().is_some_and(|entry| entry.node_info.id == id) {
+                *entry = None;
+                removed = true;
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use super::*;
+
+    fn id(id: u128) -> ChordId {
+        ChordId::from(id)
+    }
+
+    fn node(id: u128) -> NodeInfo {
+        NodeInfo::with_address(
+            ChordId::from(id),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 5000)),
+        )
+    }
+
+    fn finger_ids(table: &FingerTable) -> Vec<Option<ChordId>> {
+        table
+            .get_entries()
+            .iter()
+            .map(|entry| entry.as_ref().map(|entry| entry.node_info.id))
+            .collect()
+    }
+
+    #[test]
+    fn test_update_with() {
+        let mut table = FingerTable::new(id(100));
+        assert_eq!(table.finger_start(0), id(101));
+        assert_eq!(table.finger_start(4), id(116));
+
+        assert!(table.update_with(node(110)));
+        assert!(finger_ids(&table)
+            .iter()
+            .all(|&entry| entry == Some(id(110))));
+
+        // 104 succeeds the starts 101, 102 and 104, but not 108. Starts from 116 on wrap around
+        // the ring, where 104 comes first as well.
+        assert!(table.update_with(node(104)));
+        assert_eq!(
+            finger_ids(&table)[..5],
+            [104, 104, 104, 110, 104].map(|value| Some(id(value)))
+        );
+
+        // the owner and nodes that improve nothing are ignored
+        assert!(!table.update_with(node(100)));
+        assert!(!table.update_with(node(106)));
+        assert!(!table.update_with(node(104)));
+    }
+
+    #[test]
+    fn test_closest_preceding_and_removal() {
+        let mut table = FingerTable::new(id(100));
+        let successors = [node(110), node(120)].map(FingerTableEntry::new);
+        table.get_successors_mut().extend(successors);
+        table
+            .get_predecessors_mut()
+            .push(FingerTableEntry::new(node(90)));
+        table.update_with(node(110));
+        table.update_with(node(200));
+
+        // every node is visited once, even though 110 is a successor and a finger
+        let ids: Vec<_> = table.nodes().map(|node| node.id).collect();
+        assert_eq!(ids, [110, 120, 90, 200].map(id));
+
+        let closest = |table: &FingerTable, value| table.closest_preceding(id(value)).map(|n| n.id);
+        assert_eq!(closest(&table, 150), Some(id(120)));
+        assert_eq!(closest(&table, 95), Some(id(90)));
+        assert_eq!(closest(&table, 105), None);
+        assert_eq!(closest(&table, 100), Some(id(90)));
+        let preceding: Vec<_> = table
+            .preceding_nodes(id(250))
+            .iter()
+            .map(|node| node.id)
+            .collect();
+        assert_eq!(preceding, [200, 120, 110].map(id));
+
+        assert!(table.remove_node(id(110)));
+        assert!(!table.remove_node(id(110)));
+        assert_eq!(closest(&table, 150), Some(id(120)));
+        assert!(table.nodes().all(|node| node.id != id(110)));
+        assert_eq!(table.get_successors().len(), 1);
+    }
 }
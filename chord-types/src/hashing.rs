@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use xxhash_rust::xxh3::xxh3_128;
+
+use crate::chord_id::ChordId;
+
+/// Maps application keys and node addresses onto the ring.
+///
+/// All nodes of a ring have to use the same hasher, otherwise they disagree about which node is
+/// responsible for a key.
+pub trait KeyHasher {
+    fn hash(&self, key: &[u8]) -> ChordId;
+}
+
+/// Uses the leading bytes of the SHA-1 digest, as proposed in the Chord paper.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Sha1Hasher;
+
+impl KeyHasher for Sha1Hasher {
+    fn hash(&self, key: &[u8]) -> ChordId {
+        ChordId::from_digest(&Sha1::digest(key))
+    }
+}
+
+/// Uses the leading bytes of the SHA-256 digest.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Sha256Hasher;
+
+impl KeyHasher for Sha256Hasher {
+    fn hash(&self, key: &[u8]) -> ChordId {
+        ChordId::from_digest(&Sha256::digest(key))
+    }
+}
+
+/// Uses the 128-bit XXH3 hash, which is much faster, but not cryptographically secure.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct XxHasher;
+
+impl KeyHasher for XxHasher {
+    fn hash(&self, key: &[u8]) -> ChordId {
+        ChordId::from_digest(&xxh3_128(key).to_be_bytes())
+    }
+}
+
+/// Selects one of the [KeyHasher] implementations, e.g. in a config.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    XxHash,
+}
+
+impl KeyHasher for HashAlgorithm {
+    fn hash(&self, key: &[u8]) -> ChordId {
+        match self {
+            HashAlgorithm::Sha1 => Sha1Hasher.hash(key),
+            HashAlgorithm::Sha256 => Sha256Hasher.hash(key),
+            HashAlgorithm::XxHash => XxHasher.hash(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_uses_leading_digest_bytes() {
+        assert_eq!(
+            HashAlgorithm::Sha1.hash(b"abc"),
+            "a9993e364706816aba3e25717850c26c".parse().unwrap()
+        );
+        assert_eq!(
+            HashAlgorithm::Sha256.hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223".parse().unwrap()
+        );
+        assert_eq!(
+            HashAlgorithm::XxHash.hash(b"abc"),
+            ChordId::from(xxh3_128(b"abc"))
+        );
+    }
+}
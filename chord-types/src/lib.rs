@@ -5,5 +5,10 @@
  * license that can be found in the LICENSE file or at
  * https://opensource.org/licenses/MIT.
  */
+pub mod chord_id;
+#[cfg(feature = "serde")]
+pub mod encoding;
 pub mod finger_table;
+pub mod hashing;
 pub mod node_info;
+pub mod ring_interval;
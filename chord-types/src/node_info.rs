@@ -6,23 +6,232 @@
  * https://opensource.org/licenses/MIT.
  */
 
-use std::net::IpAddr;
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 
 use http::Uri;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+use crate::chord_id::ChordId;
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NodeInfo {
-    pub id: u64,
-    pub address: IpAddr,
-    pub port: u16,
+    pub id: ChordId,
+
+    /// The endpoints the node can be reached under, in order of preference.
+    pub endpoints: Vec<Endpoint>,
 }
 
 impl NodeInfo {
+    pub fn new(id: ChordId, endpoints: Vec<Endpoint>) -> Self {
+        Self { id, endpoints }
+    }
+
+    /// A node that is reachable under a single plain http address.
+    pub fn with_address(id: ChordId, address: SocketAddr) -> Self {
+        Self::new(id, vec![Endpoint::from(address)])
+    }
+
+    pub fn uris(&self) -> impl Iterator<Item = Uri> + '_ {
+        self.endpoints.iter().map(Endpoint::uri)
+    }
+
+    /// Identifies the process hosting the node by its preferred endpoint, which all virtual nodes
+    /// of a process share.
+    pub fn process(&self) -> String {
+        match self.endpoints.first() {
+            Some(endpoint) => endpoint.to_string(),
+            None => self.id.to_string(),
+        }
+    }
+
+    /// The first endpoint that is given as an ip address.
+    pub fn socket_address(&self) -> Option<SocketAddr> {
+        self.endpoints
+            .iter()
+            .find_map(|endpoint| match endpoint.host {
+                Host::Ip(address) => Some(SocketAddr::new(address, endpoint.port)),
+                Host::Dns(_) => None,
+            })
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Scheme {
+    #[default]
+    Http,
+    Https,
+}
+
+impl Scheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Host {
+    Ip(IpAddr),
+    Dns(String),
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Host::Ip(IpAddr::V6(address)) => write!(f, "[{}]", address),
+            Host::Ip(address) => write!(f, "{}", address),
+            Host::Dns(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Endpoint {
+    pub scheme: Scheme,
+    pub host: Host,
+    pub port: u16,
+}
+
+impl Endpoint {
+    pub fn new(scheme: Scheme, host: Host, port: u16) -> Self {
+        Self { scheme, host, port }
+    }
+
     pub fn uri(&self) -> Uri {
         Uri::builder()
-            .scheme("http")
-            .authority(format!("{}:{}", self.address, self.port))
+            .scheme(self.scheme.as_str())
+            .authority(format!("{}:{}", self.host, self.port))
+            .path_and_query("/")
             .build()
             .unwrap()
     }
 }
+
+impl From<SocketAddr> for Endpoint {
+    fn from(address: SocketAddr) -> Self {
+        Self::new(Scheme::Http, Host::Ip(address.ip()), address.port())
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}:{}", self.scheme.as_str(), self.host, self.port)
+    }
+}
+
+/// Parses endpoints like `http://10.0.0.1:7001`, `https://[::1]:7001` or `http://node-1.local`.
+///
+/// The port defaults to the one of the scheme.
+impl FromStr for Endpoint {
+    type Err = ParseEndpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let uri: Uri = s.parse().map_err(|_| ParseEndpointError::invalid_uri(s))?;
+        let scheme = match uri.scheme_str() {
+            Some("http") => Scheme::Http,
+            Some("https") => Scheme::Https,
+            Some(scheme) => return Err(ParseEndpointError::unsupported_scheme(scheme)),
+            None => return Err(ParseEndpointError::MissingScheme),
+        };
+        let host = uri.host().ok_or(ParseEndpointError::MissingHost)?;
+        let host = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+            Ok(address) => Host::Ip(address),
+            Err(_) => Host::Dns(host.to_string()),
+        };
+        let port = uri.port_u16().unwrap_or(scheme.default_port());
+        Ok(Self::new(scheme, host, port))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum ParseEndpointError {
+    #[error("invalid endpoint uri: {0}")]
+    InvalidUri(String),
+    #[error("unsupported endpoint scheme {0}, expected http or https")]
+    UnsupportedScheme(String),
+    #[error("endpoint has no scheme")]
+    MissingScheme,
+    #[error("endpoint has no host")]
+    MissingHost,
+}
+
+impl ParseEndpointError {
+    pub fn invalid_uri(uri: impl Into<String>) -> Self {
+        ParseEndpointError::InvalidUri(uri.into())
+    }
+
+    pub fn unsupported_scheme(scheme: impl Into<String>) -> Self {
+        ParseEndpointError::UnsupportedScheme(scheme.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoint() {
+        let endpoints = [
+            "http://10.0.0.1:7001",
+            "https://[::1]:7001",
+            "http://node-1.local:80",
+        ];
+        for endpoint in endpoints {
+            assert_eq!(endpoint.parse::<Endpoint>().unwrap().to_string(), endpoint);
+        }
+        assert_eq!(
+            "https://[::1]".parse(),
+            Ok(Endpoint::new(
+                Scheme::Https,
+                Host::Ip(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+                443
+            ))
+        );
+        assert_eq!(
+            "https://[::1]:7001".parse::<Endpoint>().unwrap().uri(),
+            "https://[::1]:7001/"
+        );
+        assert_eq!(
+            "ftp://node-1.local".parse::<Endpoint>(),
+            Err(ParseEndpointError::unsupported_scheme("ftp"))
+        );
+        assert_eq!(
+            "node-1.local:7001".parse::<Endpoint>(),
+            Err(ParseEndpointError::MissingScheme)
+        );
+
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, 7001));
+        let node = NodeInfo::new(
+            ChordId::ZERO,
+            vec![
+                "http://node-1.local".parse().unwrap(),
+                Endpoint::from(address),
+            ],
+        );
+        assert_eq!(node.socket_address(), Some(address));
+        assert_eq!(node.process(), "http://node-1.local:80");
+        assert_eq!(
+            NodeInfo::new(ChordId::from(1u128), Vec::new()).process(),
+            ChordId::from(1u128).to_string()
+        );
+    }
+}
@@ -0,0 +1,320 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::chord_id::Id;
+
+/// A set of ids on the ring, described by walking clockwise from a start to an end id.
+///
+/// Each end can be open or closed. If start and end are equal, the walk goes around the whole
+/// ring: `(x, x]`, `[x, x)` and `[x, x]` contain every id, and `(x, x)` every id except `x`. This
+/// is what Chord needs, e.g. a node that is its own successor is responsible for all keys.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RingInterval<const BYTES: usize> {
+    /// Contains no id.
+    Empty,
+    /// Contains every id.
+    Full,
+    Arc {
+        start: Id<BYTES>,
+        end: Id<BYTES>,
+        start_closed: bool,
+        end_closed: bool,
+    },
+}
+
+impl<const BYTES: usize> RingInterval<BYTES> {
+    pub fn new(start: Id<BYTES>, end: Id<BYTES>, start_closed: bool, end_closed: bool) -> Self {
+        RingInterval::Arc {
+            start,
+            end,
+            start_closed,
+            end_closed,
+        }
+    }
+
+    /// `(start, end)`
+    pub fn open(start: Id<BYTES>, end: Id<BYTES>) -> Self {
+        Self::new(start, end, false, false)
+    }
+
+    /// `[start, end]`
+    pub fn closed(start: Id<BYTES>, end: Id<BYTES>) -> Self {
+        Self::new(start, end, true, true)
+    }
+
+    /// `(start, end]`, e.g. the keys a node is responsible for, from its predecessor to itself.
+    pub fn open_closed(start: Id<BYTES>, end: Id<BYTES>) -> Self {
+        Self::new(start, end, false, true)
+    }
+
+    /// `[start, end)`
+    pub fn closed_open(start: Id<BYTES>, end: Id<BYTES>) -> Self {
+        Self::new(start, end, true, false)
+    }
+
+    pub fn contains(&self, id: Id<BYTES>) -> bool {
+        match *self {
+            RingInterval::Empty => false,
+            RingInterval::Full => true,
+            RingInterval::Arc {
+                start,
+                end,
+                start_closed,
+                end_closed,
+            } => {
+                let offset = start.distance_to(id);
+                if start == end {
+                    return offset != Id::ZERO || start_closed || end_closed;
+                }
+                let length = start.distance_to(end);
+                if offset == Id::ZERO {
+                    start_closed
+                } else if offset == length {
+                    end_closed
+                } else {
+                    offset < length
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            RingInterval::Empty => true,
+            RingInterval::Full => false,
+            RingInterval::Arc {
+                start,
+                end,
+                start_closed,
+                end_closed,
+            } => !start_closed && !end_closed && start.distance_to(end) == Id::from(1u64),
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        match *self {
+            RingInterval::Empty => false,
+            RingInterval::Full => true,
+            RingInterval::Arc {
+                start,
+                end,
+                start_closed,
+                end_closed,
+            } => {
+                let length = start.distance_to(end);
+                (length == Id::ZERO && (start_closed || end_closed))
+                    || (length == Id::MAX && start_closed && end_closed)
+            }
+        }
+    }
+
+    /// The clockwise distance from the start to the end of the interval.
+    ///
+    /// This is `None` if the interval goes around the whole ring, as a full turn does not fit into
+    /// an id.
+    pub fn distance(&self) -> Option<Id<BYTES>> {
+        match *self {
+            RingInterval::Empty => Some(Id::ZERO),
+            RingInterval::Full => None,
+            RingInterval::Arc { start, end, .. } if start == end => None,
+            RingInterval::Arc { start, end, .. } => Some(start.distance_to(end)),
+        }
+    }
+
+    /// The id halfway between start and end, rounded towards the start.
+    ///
+    /// This is `None` for [RingInterval::Empty] and [RingInterval::Full], which have no start.
+    pub fn midpoint(&self) -> Option<Id<BYTES>> {
+        match *self {
+            RingInterval::Arc { start, .. } => Some(match self.distance() {
+                Some(distance) => start + half(distance),
+                None => start + Id::pow2(Id::<BYTES>::BITS - 1),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<const BYTES: usize> Display for RingInterval<BYTES> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RingInterval::Empty => write!(f, "()"),
+            RingInterval::Full => write!(f, "[..]"),
+            RingInterval::Arc {
+                start,
+                end,
+                start_closed,
+                end_closed,
+            } => write!(
+                f,
+                "{}{}, {}{}",
+                if *start_closed { '[' } else { '(' },
+                start,
+                end,
+                if *end_closed { ']' } else { ')' },
+            ),
+        }
+    }
+}
+
+fn half<const BYTES: usize>(id: Id<BYTES>) -> Id<BYTES> {
+    let mut bytes = id.to_be_bytes();
+    let mut carry = 0;
+    for byte in &mut bytes {
+        let next_carry = *byte & 1;
+        *byte = (*byte >> 1) | (carry << 7);
+        carry = next_carry;
+    }
+    Id::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    type SmallId = Id<1>;
+    type MediumId = Id<2>;
+
+    /// The ids visited when walking clockwise from `start` to `end`, both included. Equal ids make
+    /// a full turn.
+    fn walk<const BYTES: usize>(start: Id<BYTES>, end: Id<BYTES>) -> Vec<Id<BYTES>> {
+        let mut ids = vec![start];
+        let mut id = start;
+        loop {
+            id = id + Id::from(1u64);
+            ids.push(id);
+            if id == end {
+                return ids;
+            }
+        }
+    }
+
+    fn model<const BYTES: usize>(interval: &RingInterval<BYTES>) -> BTreeSet<Id<BYTES>> {
+        match *interval {
+            RingInterval::Empty => BTreeSet::new(),
+            RingInterval::Full => walk(Id::ZERO, Id::ZERO).into_iter().collect(),
+            RingInterval::Arc {
+                start,
+                end,
+                start_closed,
+                end_closed,
+            } => {
+                let ids = walk(start, end);
+                let mut set: BTreeSet<_> = ids[1..ids.len() - 1].iter().copied().collect();
+                if start_closed {
+                    set.insert(start);
+                }
+                if end_closed {
+                    set.insert(end);
+                }
+                set
+            }
+        }
+    }
+
+    fn check_against_model<const BYTES: usize>(interval: RingInterval<BYTES>) {
+        let expected = model(&interval);
+        let all = walk(Id::<BYTES>::ZERO, Id::ZERO);
+        for &id in &all[1..] {
+            assert_eq!(
+                interval.contains(id),
+                expected.contains(&id),
+                "{} {}",
+                interval,
+                id
+            );
+        }
+        assert_eq!(interval.is_empty(), expected.is_empty(), "{}", interval);
+        assert_eq!(
+            interval.is_full(),
+            expected.len() == all.len() - 1,
+            "{}",
+            interval
+        );
+        if let RingInterval::Arc { start, end, .. } = interval {
+            let steps = walk(start, end).len() - 1;
+            let expected_distance = (steps < all.len() - 1).then(|| Id::from(steps as u64));
+            assert_eq!(interval.distance(), expected_distance, "{}", interval);
+            assert_eq!(
+                interval.midpoint(),
+                Some(walk(start, end)[steps / 2]),
+                "{}",
+                interval
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_small_ring_matches_model(
+            start: u8,
+            offset in prop_oneof![0u8..4, any::<u8>(), u8::MAX - 3..=u8::MAX],
+            start_closed: bool,
+            end_closed: bool,
+        ) {
+            let end = start.wrapping_add(offset);
+            let interval = RingInterval::<1>::new(
+                SmallId::from(start as u64),
+                SmallId::from(end as u64),
+                start_closed,
+                end_closed,
+            );
+            check_against_model(interval);
+        }
+    }
+
+    proptest! {
+        // every case walks the whole ring of 2^16 ids
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_medium_ring_matches_model(
+            start: u16,
+            offset in prop_oneof![0u16..4, any::<u16>(), u16::MAX - 3..=u16::MAX],
+            start_closed: bool,
+            end_closed: bool,
+        ) {
+            let end = start.wrapping_add(offset);
+            let interval = RingInterval::<2>::new(
+                MediumId::from(start as u64),
+                MediumId::from(end as u64),
+                start_closed,
+                end_closed,
+            );
+            check_against_model(interval);
+        }
+    }
+
+    #[test]
+    fn test_equal_endpoints() {
+        check_against_model(RingInterval::<1>::Empty);
+        check_against_model(RingInterval::<1>::Full);
+        let x = SmallId::from(7u64);
+        assert!(RingInterval::open_closed(x, x).contains(x));
+        assert!(RingInterval::open_closed(x, x).is_full());
+        assert!(RingInterval::closed_open(x, x).is_full());
+        assert!(!RingInterval::open(x, x).contains(x));
+        assert!(RingInterval::open(x, x).contains(x + SmallId::from(1u64)));
+        assert!(RingInterval::open(x, x + SmallId::from(1u64)).is_empty());
+        assert_eq!(
+            RingInterval::open(x, x).midpoint(),
+            Some(SmallId::from(135u64))
+        );
+        assert_eq!(RingInterval::open_closed(x, x).to_string(), "(07, 07]");
+    }
+}
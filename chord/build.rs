@@ -60,7 +60,7 @@ mod proto {
                 .into_iter()
                 .filter_map(|e| e.ok())
                 .filter(|e| e.file_type().is_file())
-                .filter(|e| e.path().extension().map_or(false, |ext| ext == "proto"))
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "proto"))
                 .map(|e| e.path().to_path_buf())
                 .collect(),
         )
@@ -78,7 +78,7 @@ mod proto {
         let new_hash = hasher.finish();
         let path = hash_path(out_dir);
         let old_hash = read_hash(&path);
-        let has_changed = old_hash.map_or(true, |old_hash| old_hash != new_hash);
+        let has_changed = old_hash.is_none_or(|old_hash| old_hash != new_hash);
         if has_changed {
             write_hash(&path, new_hash);
         }
@@ -140,7 +140,7 @@ mod completions {
         let new_hash = hasher.finish();
         let path = hash_path(out_dir);
         let old_hash = read_hash(&path);
-        let has_changed = old_hash.map_or(true, |old_hash| old_hash != new_hash);
+        let has_changed = old_hash.is_none_or(|old_hash| old_hash != new_hash);
         if has_changed {
             write_hash(&path, new_hash);
         }
@@ -181,7 +181,7 @@ mod build_rs {
             std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("build.rs")).unwrap();
         build_rs_content.hash(&mut hasher);
         let new_hash = hasher.finish();
-        let has_changed = read_hash(&path).map_or(true, |old_hash| old_hash != new_hash);
+        let has_changed = read_hash(&path).is_none_or(|old_hash| old_hash != new_hash);
         if has_changed {
             write_hash(&path, new_hash);
         }
@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use tonic::Status;
+use tonic::transport::Channel;
+
+use crate::api::com::barmetler::chord::{
+    AddNodeRequest, GetOwnershipRequest, ListNodesRequest, OwnershipReport, RebalanceRequest,
+    RemoveNodeRequest,
+};
+use crate::api::com::barmetler::chord::admin_service_client::AdminServiceClient;
+use crate::api::com::barmetler::chord::NodeInfo as NodeInfoMsg;
+use crate::args::{AdminArgs, AdminCommand};
+
+/// Runs an admin command against a running process and prints the result.
+pub async fn run_admin_command(args: AdminArgs) -> Result<(), Status> {
+    let uri = format!("http://{}", args.target);
+    let channel = Channel::from_shared(uri)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?
+        .connect()
+        .await
+        .map_err(|e| Status::unavailable(e.to_string()))?;
+    let mut client = AdminServiceClient::new(channel);
+    match args.command {
+        AdminCommand::ListNodes => {
+            let response = client.list_nodes(ListNodesRequest {}).await?.into_inner();
+            for node in &response.nodes {
+                print_node(node);
+            }
+        }
+        AdminCommand::AddNode { id } => {
+            let response = client.add_node(AddNodeRequest { id }).await?.into_inner();
+            if let Some(node) = &response.node {
+                print_node(node);
+            }
+        }
+        AdminCommand::RemoveNode { id } => {
+            client.remove_node(RemoveNodeRequest { id }).await?;
+        }
+        AdminCommand::Ownership => {
+            let response = client
+                .get_ownership(GetOwnershipRequest {})
+                .await?
+                .into_inner();
+            print_report(&response.report.unwrap_or_default());
+        }
+        AdminCommand::Rebalance => {
+            let response = client.rebalance(RebalanceRequest {}).await?.into_inner();
+            println!("Before:");
+            print_report(&response.before.unwrap_or_default());
+            println!("After:");
+            print_report(&response.after.unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+fn print_node(node: &NodeInfoMsg) {
+    if node.endpoints.is_empty() {
+        println!("{} {}:{}", node.id, node.ip, node.port);
+    } else {
+        println!("{} {}", node.id, node.endpoints.join(","));
+    }
+}
+
+/// Prints one line per process, marking the process the command was sent to with `*`.
+fn print_report(report: &OwnershipReport) {
+    println!(
+        "  {:<32} {:>5} {:>8} {:>8} {:>8} {:>8}",
+        "ENDPOINT", "NODES", "CAPACITY", "SHARE", "TARGET", "KEYS"
+    );
+    for process in &report.processes {
+        println!(
+            "{} {:<32} {:>5} {:>8.2} {:>7.2}% {:>7.2}% {:>8}",
+            if process.local { '*' } else { ' ' },
+            process.endpoint,
+            process.node_count,
+            process.capacity,
+            process.share * 100.0,
+            process.target_share * 100.0,
+            process
+                .key_count
+                .map_or_else(|| "-".to_string(), |count| count.to_string()),
+        );
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use shaku::{Component, Interface};
+use tonic::{Request, Response, Status};
+
+use crate::api::com::barmetler::chord::{
+    AddNodeRequest, AddNodeResponse, GetOwnershipRequest, GetOwnershipResponse, ListNodesRequest,
+    ListNodesResponse, RebalanceRequest, RebalanceResponse, RemoveNodeRequest, RemoveNodeResponse,
+};
+use crate::api::com::barmetler::chord::admin_service_server::AdminService;
+use crate::convert::ToProto;
+use crate::node_grpc_service::id_from_string;
+use crate::node_manager::{NodeManager, NodeManagerError};
+use crate::rebalancer::{RebalanceError, Rebalancer};
+
+pub trait AdminGrpcServiceComponent: AdminService + Interface {}
+
+/// Lets operators manage the virtual nodes hosted by this process.
+#[derive(Component)]
+#[shaku(interface = AdminGrpcServiceComponent)]
+pub struct AdminGrpcService {
+    #[shaku(inject)]
+    node_manager: Arc<dyn NodeManager>,
+
+    #[shaku(inject)]
+    rebalancer: Arc<dyn Rebalancer>,
+}
+
+impl AdminGrpcServiceComponent for AdminGrpcService {}
+
+#[async_trait]
+impl AdminService for AdminGrpcService {
+    async fn add_node(
+        &self,
+        request: Request<AddNodeRequest>,
+    ) -> Result<Response<AddNodeResponse>, Status> {
+        let request = request.into_inner();
+        let id = request.id.map(id_from_string).transpose()?;
+        let node = self.node_manager.add_node(id).await?;
+        Ok(Response::new(AddNodeResponse {
+            node: Some(node.node_info().to_proto()),
+        }))
+    }
+
+    async fn remove_node(
+        &self,
+        request: Request<RemoveNodeRequest>,
+    ) -> Result<Response<RemoveNodeResponse>, Status> {
+        let request = request.into_inner();
+        let id = id_from_string(request.id)?;
+        self.node_manager.remove_node(id).await?;
+        Ok(Response::new(RemoveNodeResponse {}))
+    }
+
+    async fn list_nodes(
+        &self,
+        _request: Request<ListNodesRequest>,
+    ) -> Result<Response<ListNodesResponse>, Status> {
+        let mut nodes = self.node_manager.list_nodes();
+        nodes.sort_by_key(|node| node.id());
+        Ok(Response::new(ListNodesResponse {
+            nodes: nodes
+                .iter()
+                .map(|node| node.node_info().to_proto())
+                .collect(),
+        }))
+    }
+
+    async fn get_ownership(
+        &self,
+        _request: Request<GetOwnershipRequest>,
+    ) -> Result<Response<GetOwnershipResponse>, Status> {
+        let report = self.rebalancer.report().await?;
+        Ok(Response::new(GetOwnershipResponse {
+            report: Some(report.to_proto()),
+        }))
+    }
+
+    async fn rebalance(
+        &self,
+        _request: Request<RebalanceRequest>,
+    ) -> Result<Response<RebalanceResponse>, Status> {
+        let outcome = self.rebalancer.rebalance().await?;
+        Ok(Response::new(RebalanceResponse {
+            before: Some(outcome.before.to_proto()),
+            after: Some(outcome.after.to_proto()),
+        }))
+    }
+}
+
+impl From<NodeManagerError> for Status {
+    fn from(value: NodeManagerError) -> Self {
+        Status::new(value.get_code(), value.to_string())
+    }
+}
+
+impl From<RebalanceError> for Status {
+    fn from(value: RebalanceError) -> Self {
+        Status::new(value.get_code(), value.to_string())
+    }
+}
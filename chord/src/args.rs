@@ -12,6 +12,7 @@
  */
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use clap::Parser;
 
@@ -22,6 +23,24 @@ pub struct Args {
     #[arg(short = 'n', long, default_value = "1")]
     pub virtual_nodes: u32,
 
+    /// The hash function that maps node addresses and keys onto the ring.
+    ///
+    /// All processes of a ring have to use the same one.
+    #[arg(
+        long = "hash",
+        value_name = "ALGORITHM",
+        default_value = "sha1",
+        value_parser = ["sha1", "sha256", "xxhash"]
+    )]
+    pub hash_algorithm: String,
+
+    /// Explicit ids of the virtual nodes, as hexadecimal numbers.
+    ///
+    /// The first id is used for the first virtual node, and so on. Virtual nodes without an
+    /// explicit id get one derived from the advertised address.
+    #[arg(long = "id", value_name = "ID", value_delimiter = ',')]
+    pub node_ids: Vec<String>,
+
     /// The addresses to bind the grpc interfaces to.
     ///
     /// Supports both IPv4 and IPv6 addresses.
@@ -32,4 +51,214 @@ pub struct Args {
         value_delimiter = ','
     )]
     pub socket_addresses: Vec<SocketAddr>,
+
+    /// The address other nodes should use to reach this process.
+    ///
+    /// Defaults to the first address the grpc interfaces are bound to. Unspecified addresses like
+    /// `0.0.0.0` or port 0 are rejected, since the ids of the virtual nodes are derived from it.
+    #[arg(long = "advertise", value_name = "ADDRESS")]
+    pub advertised_address: Option<SocketAddr>,
+
+    /// Further endpoints other nodes can use to reach this process, tried in order after the
+    /// advertised address.
+    ///
+    /// For example `http://node-1.local:7001` or `http://[2001:db8::1]:7001`.
+    #[arg(long = "advertise-endpoint", value_name = "URI", value_delimiter = ',')]
+    pub advertised_endpoints: Vec<String>,
+
+    /// The addresses of processes that are already part of a ring.
+    ///
+    /// They are tried in order until one of them can be joined. If none are given, a new ring is
+    /// created.
+    #[arg(
+        short = 'j',
+        long = "join",
+        value_name = "ADDRESS",
+        value_delimiter = ','
+    )]
+    pub join_addresses: Vec<SocketAddr>,
+
+    /// The capacity of this process relative to the other processes of the ring.
+    ///
+    /// The virtual nodes are rebalanced so that each process owns the share of the ring that its
+    /// capacity has of the total capacity.
+    #[arg(long, value_name = "CAPACITY", default_value = "1.0")]
+    pub capacity: f64,
+
+    /// Rebalance the virtual nodes every this many seconds.
+    ///
+    /// If not given, rebalancing only happens when requested through `admin rebalance`.
+    #[arg(long, value_name = "SECONDS")]
+    pub rebalance_interval: Option<u64>,
+
+    /// Let the virtual nodes of this process share the nodes they learn about, so that lookups
+    /// from any of them can start at the closest node known to the process.
+    #[arg(long)]
+    pub shared_routing: bool,
+
+    /// The directory to store the key-value pairs of the virtual nodes in.
+    ///
+    /// Each node uses a subdirectory named after its id, and recovers its data from there after a
+    /// restart. If not given, the data is only kept in memory.
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// When to flush the write-ahead log of the data directory to disk.
+    ///
+    /// `batched` flushes after a number of writes, so a crash of the machine may lose the most
+    /// recent ones.
+    #[arg(
+        long = "fsync",
+        value_name = "POLICY",
+        default_value = "batched",
+        value_parser = ["always", "batched", "never"]
+    )]
+    pub fsync_policy: String,
+
+    /// With the `batched` fsync policy, the number of writes after which the write-ahead log is
+    /// flushed.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "64",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub fsync_max_pending: u32,
+
+    /// With the `batched` fsync policy, how long a write may wait for the write-ahead log to be
+    /// flushed.
+    #[arg(long, value_name = "MILLISECONDS", default_value = "1000")]
+    pub fsync_max_delay: u64,
+
+    /// The number of nodes storing each key: its owner, and successors of the owner hosted by
+    /// other processes.
+    ///
+    /// All processes of a ring should use the same value.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub replicas: u32,
+
+    /// How many of the nodes storing a key have to respond to a read, unless a request asks for
+    /// another level.
+    ///
+    /// Either `one`, `quorum`, `all`, or a number of nodes up to the number of replicas.
+    #[arg(long, value_name = "LEVEL", default_value = "one")]
+    pub read_consistency: String,
+
+    /// How many of the nodes storing a key have to acknowledge a write, unless a request asks for
+    /// another level.
+    ///
+    /// Reads see the latest successful write if the read and write levels add up to more than the
+    /// number of replicas, unless the nodes storing the key changed in between.
+    #[arg(long, value_name = "LEVEL", default_value = "one")]
+    pub write_consistency: String,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Manages the virtual nodes of a running process instead of starting one.
+    Admin(AdminArgs),
+
+    /// Looks up, reads and writes keys of the ring a running process is part of, instead of starting one.
+    ///
+    /// The ring has to use the hash algorithm given by `--hash`. If the owner of a key does not
+    /// respond, its copies are read from the number of nodes `--replicas` and `--read-consistency`
+    /// ask for.
+    Kv(KeyValueArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AdminArgs {
+    /// The address of the process to manage.
+    #[arg(short = 't', long = "target", value_name = "ADDRESS")]
+    pub target: SocketAddr,
+
+    #[command(subcommand)]
+    pub command: AdminCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum AdminCommand {
+    /// Lists the virtual nodes hosted by the process.
+    ListNodes,
+
+    /// Adds a virtual node, which joins the ring right away.
+    AddNode {
+        /// An explicit id for the node, as a hexadecimal number.
+        ///
+        /// Defaults to an id derived from the advertised address and the next virtual node index.
+        #[arg(long = "id", value_name = "ID")]
+        id: Option<String>,
+    },
+
+    /// Lets a virtual node leave the ring gracefully and removes it.
+    RemoveNode {
+        /// The id of the node, as a hexadecimal number.
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+
+    /// Shows which share of the ring each process owns.
+    Ownership,
+
+    /// Adjusts the virtual nodes of the process until it owns its target share of the ring, and
+    /// shows the ownership before and after.
+    Rebalance,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct KeyValueArgs {
+    /// The address of the process to start the lookups at.
+    #[arg(short = 't', long = "target", value_name = "ADDRESS")]
+    pub target: SocketAddr,
+
+    #[command(subcommand)]
+    pub command: KeyValueCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum KeyValueCommand {
+    /// Prints the node responsible for a key, and the number of hops it took to find it.
+    Lookup {
+        #[arg(value_name = "KEY")]
+        key: String,
+    },
+
+    /// Prints the value stored under a key.
+    Get {
+        #[arg(value_name = "KEY")]
+        key: String,
+
+        /// How many of the nodes storing the key have to respond, instead of the default level of
+        /// its owner.
+        #[arg(long, value_name = "LEVEL")]
+        consistency: Option<String>,
+    },
+
+    /// Stores a value under a key.
+    Put {
+        #[arg(value_name = "KEY")]
+        key: String,
+
+        #[arg(value_name = "VALUE")]
+        value: String,
+
+        /// How many of the nodes storing the key have to acknowledge the write, instead of the
+        /// default level of its owner.
+        #[arg(long, value_name = "LEVEL")]
+        consistency: Option<String>,
+    },
+
+    /// Removes a key.
+    Delete {
+        #[arg(value_name = "KEY")]
+        key: String,
+    },
 }
@@ -6,13 +6,18 @@
  * https://opensource.org/licenses/MIT.
  */
 
-use std::net::{SocketAddrV4, SocketAddrV6};
+use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use shaku::{Component, Interface};
 
+use chord_types::chord_id::ChordId;
+use chord_types::hashing::HashAlgorithm;
+use chord_types::node_info::Endpoint;
+
 pub trait ConfigProvider: Interface {
     fn get_config(&self) -> Arc<Config>;
 }
@@ -29,20 +34,336 @@ impl ConfigProvider for DefaultConfigProvider {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Config {
     pub virtual_nodes: u32,
+    /// The hash function that maps node addresses and keys onto the ring.
+    ///
+    /// All nodes of a ring have to use the same one.
+    pub hash_algorithm: HashAlgorithm,
+    pub node_id_config: NodeIdConfig,
     pub peer_interfaces: Vec<PeerInterface>,
+    /// The address under which the nodes of this process are reachable by other nodes.
+    pub advertised_address: Option<SocketAddr>,
+    /// Further endpoints the nodes of this process are reachable under, e.g. a hostname or an
+    /// address of the other ip family. Peers try them in order, after the advertised address.
+    pub advertised_endpoints: Vec<Endpoint>,
+    /// Addresses of processes that are part of the ring to join.
+    /// If empty, a new ring is created.
+    pub join_addresses: Vec<SocketAddr>,
     pub client_config: ClientConfig,
+    pub membership_config: MembershipConfig,
+    pub lookup_config: LookupConfig,
+    pub maintenance_config: MaintenanceConfig,
+    pub failure_detector_config: FailureDetectorConfig,
+    pub rebalance_config: RebalanceConfig,
+    pub routing_cache_config: RoutingCacheConfig,
+    pub storage_config: StorageConfig,
+    pub replication_config: ReplicationConfig,
+    pub consistency_config: ConsistencyConfig,
+    pub handoff_config: HandoffConfig,
 }
 
+/// Determines the ids of the virtual nodes hosted by this process.
+///
+/// Ids that are not pinned are derived by hashing the advertised address and the index of the
+/// virtual node, so that a restarted process takes the same positions in the ring.
 #[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct NodeIdConfig {
+    /// Explicit ids of the first virtual nodes, by index.
+    pub pinned_ids: Vec<ChordId>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ClientConfig {
     pub connect_timeout: Duration,
     pub request_timeout: Duration,
     pub keep_alive_timeout: Duration,
 }
 
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(10),
+            keep_alive_timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MembershipConfig {
+    /// How long the nodes may take to leave the ring on shutdown, before the servers are stopped
+    /// regardless.
+    pub drain_deadline: Duration,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        Self {
+            drain_deadline: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LookupConfig {
+    /// The maximum number of hops a lookup may take before it is aborted.
+    pub max_hops: u32,
+}
+
+impl Default for LookupConfig {
+    fn default() -> Self {
+        Self { max_hops: 64 }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// How often each node verifies its successor and notifies it.
+    pub stabilize_interval: Duration,
+    /// How often a single finger is refreshed.
+    pub fix_fingers_interval: Duration,
+    pub fix_fingers_strategy: FixFingersStrategy,
+    /// The number of successors and predecessors each node keeps track of (r).
+    ///
+    /// The ring survives up to r - 1 simultaneous failures of adjacent nodes.
+    pub neighbor_list_length: usize,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            stabilize_interval: Duration::from_secs(1),
+            fix_fingers_interval: Duration::from_millis(100),
+            fix_fingers_strategy: FixFingersStrategy::RoundRobin,
+            neighbor_list_length: 4,
+        }
+    }
+}
+
+/// Determines which finger is refreshed next.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FixFingersStrategy {
+    /// Refresh the fingers one after another.
+    RoundRobin,
+    /// Refresh a randomly chosen finger.
+    Random,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FailureDetectorConfig {
+    /// How long the status of a node is cached before it is pinged again.
+    pub status_lifespan: Duration,
+    /// The maximum number of nodes whose status and history are kept.
+    pub cache_size: usize,
+    pub kind: FailureDetectorKind,
+}
+
+impl Default for FailureDetectorConfig {
+    fn default() -> Self {
+        Self {
+            status_lifespan: Duration::from_secs(5),
+            cache_size: 1024,
+            kind: FailureDetectorKind::PhiAccrual {
+                threshold: 8.0,
+                window_size: 100,
+                min_std_deviation: Duration::from_millis(500),
+                first_heartbeat_estimate: Duration::from_secs(5),
+            },
+        }
+    }
+}
+
+/// Determines how a node that does not respond to a ping is judged.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FailureDetectorKind {
+    /// Suspect the node until it has not responded for `dead_after`.
+    Timeout { dead_after: Duration },
+    /// Suspect the node until the time since its last response is too unlikely, compared to the
+    /// previous response intervals.
+    PhiAccrual {
+        /// The phi value from which on a node is considered dead.
+        threshold: f64,
+        /// The number of response intervals to base the distribution on.
+        window_size: usize,
+        min_std_deviation: Duration,
+        /// The assumed response interval, before any responses were received.
+        first_heartbeat_estimate: Duration,
+    },
+}
+
+/// Determines how the virtual nodes of this process are adjusted to own a fair share of the ring.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RebalanceConfig {
+    /// The capacity of this process relative to the other processes of the ring.
+    ///
+    /// Each process aims to own the fraction of the ring that its capacity has of the total
+    /// capacity of all processes.
+    pub capacity: f64,
+    /// How far the owned share may deviate from the target share, relative to the target share,
+    /// before virtual nodes are added, removed or moved.
+    pub tolerance: f64,
+    /// The maximum number of changes to the virtual nodes in a single rebalancing.
+    pub max_rounds: u32,
+    /// How long to wait after each change, so that stabilization makes it known to the ring.
+    pub settle_time: Duration,
+    /// How often to rebalance. If not set, rebalancing only happens when requested.
+    pub interval: Option<Duration>,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1.0,
+            tolerance: 0.1,
+            max_rounds: 8,
+            settle_time: Duration::from_secs(3),
+            interval: None,
+        }
+    }
+}
+
+/// Determines whether the virtual nodes of this process share what they learn about the ring.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RoutingCacheConfig {
+    /// If set, lookups from any virtual node may take a next hop that another virtual node of this
+    /// process has learned about.
+    pub enabled: bool,
+    /// The maximum number of nodes that are kept.
+    pub capacity: usize,
+}
+
+impl Default for RoutingCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 1024,
+        }
+    }
+}
+
+/// Determines how many copies of each key-value pair the ring keeps.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    /// The number of nodes storing each key (N): its owner, and the next N - 1 successors that
+    /// are hosted by distinct processes.
+    ///
+    /// The successor list has to be long enough to contain that many processes, see
+    /// [required_neighbors](crate::replication::required_neighbors). Otherwise, fewer replicas are
+    /// kept.
+    pub replication_factor: usize,
+    /// The maximum number of entries sent in a single request when replicas are re-created.
+    pub batch_size: usize,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            replication_factor: 1,
+            batch_size: 256,
+        }
+    }
+}
+
+/// The consistency levels of requests that do not ask for one.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ConsistencyConfig {
+    pub read: Consistency,
+    pub write: Consistency,
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            read: Consistency::One,
+            write: Consistency::One,
+        }
+    }
+}
+
+/// The number of nodes storing a key that have to respond to a read, or acknowledge a write,
+/// before the request succeeds.
+///
+/// Reads return the copy with the newest version among the nodes that responded. If the levels of
+/// both add up to more than the replication factor (R + W > N), that includes the latest successful
+/// write, as long as the nodes storing the key do not change in between.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Consistency {
+    /// Only the owner of the key. Writes reach the replicas in the background.
+    One,
+    /// A majority of the N nodes storing the key.
+    Quorum,
+    /// All N nodes storing the key.
+    All,
+    /// An explicit number of nodes, between 1 and N.
+    Count(usize),
+}
+
+/// Determines how fast a node hands the keys of an arc over to a node that joined in front of it.
+///
+/// The transfer is throttled, so that it does not starve the requests the node serves meanwhile.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct HandoffConfig {
+    /// The maximum number of entries sent in a single message of the transfer.
+    pub batch_size: usize,
+    /// How long to pause between two messages of the transfer.
+    pub batch_delay: Duration,
+}
+
+impl Default for HandoffConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 128,
+            batch_delay: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Determines where the nodes of this process keep their key-value pairs.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// The directory the nodes store their data in, each in a subdirectory named after its id.
+    ///
+    /// If not set, the data is only kept in memory and lost when the process exits.
+    pub data_dir: Option<PathBuf>,
+    pub fsync_policy: FsyncPolicy,
+    /// The number of mutations appended to the write-ahead log, after which a snapshot is written
+    /// and the log is cleared.
+    pub snapshot_threshold: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            fsync_policy: FsyncPolicy::Batched {
+                max_pending: 64,
+                max_delay: Duration::from_secs(1),
+            },
+            snapshot_threshold: 10_000,
+        }
+    }
+}
+
+/// Determines when the write-ahead log is flushed to disk.
+///
+/// Every mutation is written to the log before it is acknowledged, so it survives a crash of the
+/// process either way. Only a crash of the machine can lose mutations that were not flushed yet.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FsyncPolicy {
+    /// Flush after every mutation.
+    Always,
+    /// Flush once `max_pending` mutations have not been flushed, or the oldest of them has waited
+    /// for `max_delay`.
+    Batched {
+        max_pending: usize,
+        max_delay: Duration,
+    },
+    /// Leave flushing to the operating system.
+    Never,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum PeerInterface {
     GrpcIpV4(SocketAddrV4),
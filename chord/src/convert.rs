@@ -7,60 +7,30 @@
  */
 
 use std::convert::Infallible;
+use std::net::SocketAddr;
 
-use async_trait::async_trait;
 use thiserror::Error;
 
-use chord_types::node_info::NodeInfo;
+use chord_types::chord_id::ParseIdError;
+use chord_types::hashing::HashAlgorithm;
+use chord_types::node_info::{NodeInfo, ParseEndpointError};
 
+use crate::api::com::barmetler::chord::consistency::Level as ConsistencyLevelMsg;
+use crate::api::com::barmetler::chord::Consistency as ConsistencyMsg;
+use crate::api::com::barmetler::chord::ConsistencyLevel as NamedConsistencyMsg;
+use crate::api::com::barmetler::chord::HashAlgorithm as HashAlgorithmMsg;
 use crate::api::com::barmetler::chord::NodeInfo as NodeInfoMsg;
+use crate::api::com::barmetler::chord::OwnershipReport as OwnershipReportMsg;
+use crate::api::com::barmetler::chord::ProcessOwnership as ProcessOwnershipMsg;
+use crate::api::com::barmetler::chord::Replica as ReplicaMsg;
+use crate::config::Consistency;
+use crate::rebalancer::{OwnershipReport, ProcessOwnership};
+use crate::replication::Replica;
 
 pub trait ToProto<T> {
     fn to_proto(&self) -> T;
 }
 
-pub trait TryToProto<T> {
-    type Error: std::error::Error;
-
-    fn try_to_proto(&self) -> Result<T, Self::Error>;
-}
-
-#[async_trait]
-pub trait ToProtoAsync<T> {
-    async fn to_proto(&self) -> T;
-}
-
-#[async_trait]
-pub trait TryToProtoAsync<T> {
-    type Error: std::error::Error;
-
-    async fn try_to_proto(&self) -> Result<T, Self::Error>;
-}
-
-impl<T, F: ToProto<T>> TryToProto<T> for F {
-    type Error = Infallible;
-
-    fn try_to_proto(&self) -> Result<T, Self::Error> {
-        Ok(self.to_proto())
-    }
-}
-
-#[async_trait]
-impl<T, F: ToProtoAsync<T> + Send + Sync> TryToProtoAsync<T> for F {
-    type Error = Infallible;
-
-    async fn try_to_proto(&self) -> Result<T, Self::Error> {
-        Ok(self.to_proto().await)
-    }
-}
-
-#[async_trait]
-impl<T, F: ToProto<T> + Send + Sync> ToProtoAsync<T> for F {
-    async fn to_proto(&self) -> T {
-        self.to_proto()
-    }
-}
-
 pub trait ToDomain<T> {
     fn to_domain(&self) -> T;
 }
@@ -71,18 +41,6 @@ pub trait TryToDomain<T> {
     fn try_to_domain(&self) -> Result<T, Self::Error>;
 }
 
-#[async_trait]
-pub trait ToDomainAsync<T> {
-    async fn to_domain(&self) -> T;
-}
-
-#[async_trait]
-pub trait TryToDomainAsync<T> {
-    type Error: std::error::Error;
-
-    async fn try_to_domain(&self) -> Result<T, Self::Error>;
-}
-
 impl<T, F: ToDomain<T>> TryToDomain<T> for F {
     type Error = Infallible;
 
@@ -91,50 +49,177 @@ impl<T, F: ToDomain<T>> TryToDomain<T> for F {
     }
 }
 
-#[async_trait]
-impl<T, F: ToDomainAsync<T> + Send + Sync> TryToDomainAsync<T> for F {
-    type Error = Infallible;
-
-    async fn try_to_domain(&self) -> Result<T, Self::Error> {
-        Ok(self.to_domain().await)
-    }
-}
-
-#[async_trait]
-impl<T, F: ToDomain<T> + Send + Sync> ToDomainAsync<T> for F {
-    async fn to_domain(&self) -> T {
-        self.to_domain()
-    }
-}
-
 #[derive(Debug, Clone, Error)]
 pub enum ConversionError {
     #[error(transparent)]
     ParseIntError(#[from] std::num::ParseIntError),
     #[error(transparent)]
+    ParseIdError(#[from] ParseIdError),
+    #[error(transparent)]
     AddrParseError(#[from] std::net::AddrParseError),
+    #[error(transparent)]
+    ParseEndpointError(#[from] ParseEndpointError),
     #[error("conversion failed: {0}")]
     ConversionFailed(String),
 }
 
 impl ToProto<NodeInfoMsg> for NodeInfo {
     fn to_proto(&self) -> NodeInfoMsg {
+        let address = self.socket_address();
         NodeInfoMsg {
             id: self.id.to_string(),
-            ip: self.address.to_string(),
-            port: self.port as u32,
+            ip: address
+                .map(|address| address.ip().to_string())
+                .unwrap_or_default(),
+            port: address
+                .map(|address| address.port() as u32)
+                .unwrap_or_default(),
+            endpoints: self.endpoints.iter().map(ToString::to_string).collect(),
         }
     }
 }
 
+/// Peers that do not send endpoints are reachable under their ip and port via http.
 impl TryToDomain<NodeInfo> for NodeInfoMsg {
     type Error = ConversionError;
 
     fn try_to_domain(&self) -> Result<NodeInfo, Self::Error> {
-        Ok(NodeInfo {
-            id: self.id.parse()?,
-            address: self.ip.parse()?,
-            port: self.port as u16,
-        })
+        let id = self.id.parse()?;
+        if self.endpoints.is_empty() {
+            let address = SocketAddr::new(self.ip.parse()?, self.port as u16);
+            return Ok(NodeInfo::with_address(id, address));
+        }
+        let endpoints = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.parse())
+            .collect::<Result<_, _>>()?;
+        Ok(NodeInfo::new(id, endpoints))
+    }
+}
+
+impl ToProto<HashAlgorithmMsg> for HashAlgorithm {
+    fn to_proto(&self) -> HashAlgorithmMsg {
+        match self {
+            HashAlgorithm::Sha1 => HashAlgorithmMsg::Sha1,
+            HashAlgorithm::Sha256 => HashAlgorithmMsg::Sha256,
+            HashAlgorithm::XxHash => HashAlgorithmMsg::XxHash,
+        }
+    }
+}
+
+impl ToDomain<HashAlgorithm> for HashAlgorithmMsg {
+    fn to_domain(&self) -> HashAlgorithm {
+        match self {
+            HashAlgorithmMsg::Sha1 => HashAlgorithm::Sha1,
+            HashAlgorithmMsg::Sha256 => HashAlgorithm::Sha256,
+            HashAlgorithmMsg::XxHash => HashAlgorithm::XxHash,
+        }
+    }
+}
+
+impl ToProto<OwnershipReportMsg> for OwnershipReport {
+    fn to_proto(&self) -> OwnershipReportMsg {
+        OwnershipReportMsg {
+            processes: self.processes.iter().map(ToProto::to_proto).collect(),
+        }
+    }
+}
+
+impl ToProto<ProcessOwnershipMsg> for ProcessOwnership {
+    fn to_proto(&self) -> ProcessOwnershipMsg {
+        ProcessOwnershipMsg {
+            endpoint: self.endpoint.clone(),
+            local: self.local,
+            node_count: self.node_count as u32,
+            capacity: self.capacity,
+            share: self.share,
+            target_share: self.target_share,
+            key_count: self.key_count.map(|count| count as u64),
+        }
+    }
+}
+
+impl ToProto<ReplicaMsg> for Replica {
+    fn to_proto(&self) -> ReplicaMsg {
+        ReplicaMsg {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            version: self.version,
+        }
+    }
+}
+
+impl ToDomain<Replica> for ReplicaMsg {
+    fn to_domain(&self) -> Replica {
+        Replica {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            version: self.version,
+        }
+    }
+}
+
+impl ToProto<ConsistencyMsg> for Consistency {
+    fn to_proto(&self) -> ConsistencyMsg {
+        let level = match self {
+            Consistency::One => ConsistencyLevelMsg::Named(NamedConsistencyMsg::One as i32),
+            Consistency::Quorum => ConsistencyLevelMsg::Named(NamedConsistencyMsg::Quorum as i32),
+            Consistency::All => ConsistencyLevelMsg::Named(NamedConsistencyMsg::All as i32),
+            Consistency::Count(count) => ConsistencyLevelMsg::Count(*count as u32),
+        };
+        ConsistencyMsg { level: Some(level) }
+    }
+}
+
+impl TryToDomain<Consistency> for ConsistencyMsg {
+    type Error = ConversionError;
+
+    fn try_to_domain(&self) -> Result<Consistency, Self::Error> {
+        match self.level {
+            Some(ConsistencyLevelMsg::Named(level)) => match NamedConsistencyMsg::try_from(level) {
+                Ok(NamedConsistencyMsg::One) => Ok(Consistency::One),
+                Ok(NamedConsistencyMsg::Quorum) => Ok(Consistency::Quorum),
+                Ok(NamedConsistencyMsg::All) => Ok(Consistency::All),
+                Err(_) => Err(ConversionError::ConversionFailed(format!(
+                    "unknown consistency level: {}",
+                    level
+                ))),
+            },
+            Some(ConsistencyLevelMsg::Count(count)) => Ok(Consistency::Count(count as usize)),
+            None => Err(ConversionError::ConversionFailed(
+                "missing consistency level".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use chord_types::chord_id::ChordId;
+
+    use super::{NodeInfo, NodeInfoMsg, SocketAddr, ToProto, TryToDomain};
+
+    #[test]
+    fn test_node_info_old_and_new_form() {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, 7001));
+        let node = NodeInfo::new(
+            ChordId::from(42u64),
+            vec!["https://node-1.local:7001".parse().unwrap(), address.into()],
+        );
+        let message = node.to_proto();
+        assert_eq!((message.ip.as_str(), message.port), ("127.0.0.1", 7001));
+        assert_eq!(message.try_to_domain().unwrap(), node);
+
+        let old = NodeInfoMsg {
+            endpoints: Vec::new(),
+            ..message
+        };
+        assert_eq!(
+            old.try_to_domain().unwrap(),
+            NodeInfo::with_address(node.id, address)
+        );
     }
 }
@@ -0,0 +1,188 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use cached::{Cached, SizedCache};
+use futures::future::{ready, BoxFuture, Shared};
+use futures::FutureExt;
+use log::debug;
+use shaku::{Component, Interface};
+
+use chord_types::node_info::NodeInfo;
+
+use crate::config::{ConfigProvider, FailureDetectorKind};
+use crate::failure_detector::phi_accrual::PhiAccrualSuspicion;
+use crate::failure_detector::timeout::TimeoutSuspicion;
+use crate::node_client_factory::NodeClientFactory;
+
+pub mod phi_accrual;
+pub mod timeout;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NodeStatus {
+    Alive,
+    /// The node did not respond recently, but it is too early to consider it dead.
+    Suspected,
+    Dead,
+}
+
+#[async_trait]
+pub trait FailureDetector: Interface {
+    /// Determines the status of a remote node.
+    ///
+    /// The status is cached, so the node is only pinged once the cached status has expired.
+    async fn check_node(&self, node_info: &NodeInfo) -> NodeStatus;
+
+    /// Records that a node has left the ring, so that it is considered dead until its cached
+    /// status expires.
+    fn report_left(&self, node_info: &NodeInfo);
+}
+
+/// Judges whether an unresponsive node has failed, based on its history of heartbeats.
+pub trait Suspicion: Send {
+    /// Records a successful ping at `now`.
+    fn heartbeat(&mut self, now: Instant);
+
+    /// Determines the status of the node at `now`, after it did not respond to a ping.
+    ///
+    /// This is either [NodeStatus::Suspected] or [NodeStatus::Dead].
+    fn status_without_response(&self, now: Instant) -> NodeStatus;
+}
+
+type StatusFuture = Shared<BoxFuture<'static, NodeStatus>>;
+/// The pending or completed ping of each node, with the time it was started.
+type Statuses = SizedCache<NodeInfo, (Instant, StatusFuture)>;
+type Histories = Arc<Mutex<SizedCache<NodeInfo, Box<dyn Suspicion>>>>;
+
+/// A [FailureDetector] that pings nodes on demand and interprets missing responses according to
+/// the configured [FailureDetectorKind].
+#[derive(Component)]
+#[shaku(interface = FailureDetector)]
+pub struct PingFailureDetector {
+    #[shaku(inject)]
+    client_factory: Arc<dyn NodeClientFactory>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+
+    #[shaku(default)]
+    statuses: OnceLock<Mutex<Statuses>>,
+
+    #[shaku(default)]
+    histories: OnceLock<Histories>,
+}
+
+impl PingFailureDetector {
+    fn statuses(&self) -> MutexGuard<'_, Statuses> {
+        self.statuses
+            .get_or_init(|| {
+                let config = &self.config_provider.get_config().failure_detector_config;
+                Mutex::new(SizedCache::with_size(config.cache_size))
+            })
+            .lock()
+            .unwrap()
+    }
+
+    fn histories(&self) -> Histories {
+        self.histories
+            .get_or_init(|| {
+                let config = &self.config_provider.get_config().failure_detector_config;
+                Arc::new(Mutex::new(SizedCache::with_size(config.cache_size)))
+            })
+            .clone()
+    }
+
+    fn ping(&self, node_info: NodeInfo) -> StatusFuture {
+        let node = self.client_factory.create_node_client(&node_info);
+        let histories = self.histories();
+        let kind = self
+            .config_provider
+            .get_config()
+            .failure_detector_config
+            .kind
+            .clone();
+        async move {
+            let result = node.ping().await;
+            let now = Instant::now();
+            let mut histories = histories.lock().unwrap();
+            let history =
+                histories.cache_get_or_set_with(node_info.clone(), || new_suspicion(&kind, now));
+            match result {
+                Ok(()) => {
+                    history.heartbeat(now);
+                    NodeStatus::Alive
+                }
+                Err(e) => {
+                    let status = history.status_without_response(now);
+                    debug!(
+                        "node {} did not respond ({:?}): {}",
+                        node_info.id, status, e
+                    );
+                    status
+                }
+            }
+        }
+        .boxed()
+        .shared()
+    }
+}
+
+#[async_trait]
+impl FailureDetector for PingFailureDetector {
+    async fn check_node(&self, node_info: &NodeInfo) -> NodeStatus {
+        let lifespan = self
+            .config_provider
+            .get_config()
+            .failure_detector_config
+            .status_lifespan;
+        let now = Instant::now();
+        let status = {
+            let mut statuses = self.statuses();
+            match statuses.cache_get(node_info) {
+                Some((since, status)) if now.duration_since(*since) < lifespan => status.clone(),
+                _ => {
+                    let status = self.ping(node_info.clone());
+                    statuses.cache_set(node_info.clone(), (now, status.clone()));
+                    status
+                }
+            }
+        };
+        status.await
+    }
+
+    fn report_left(&self, node_info: &NodeInfo) {
+        self.histories().lock().unwrap().cache_remove(node_info);
+        self.statuses().cache_set(
+            node_info.clone(),
+            (Instant::now(), ready(NodeStatus::Dead).boxed().shared()),
+        );
+    }
+}
+
+fn new_suspicion(kind: &FailureDetectorKind, now: Instant) -> Box<dyn Suspicion> {
+    match kind {
+        FailureDetectorKind::Timeout { dead_after } => {
+            Box::new(TimeoutSuspicion::new(*dead_after, now))
+        }
+        FailureDetectorKind::PhiAccrual {
+            threshold,
+            window_size,
+            min_std_deviation,
+            first_heartbeat_estimate,
+        } => Box::new(PhiAccrualSuspicion::new(
+            *threshold,
+            *window_size,
+            *min_std_deviation,
+            *first_heartbeat_estimate,
+            now,
+        )),
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::failure_detector::{NodeStatus, Suspicion};
+
+/// The phi accrual failure detector by Hayashibara et al.
+///
+/// Instead of a fixed timeout, the time since the last heartbeat is compared to the distribution
+/// of previous inter-arrival times. Phi expresses how unlikely it is that the node is still alive:
+/// a phi of 1 means a 10% chance of a false positive, 2 means 1%, and so on.
+pub struct PhiAccrualSuspicion {
+    threshold: f64,
+    window_size: usize,
+    min_std_deviation: Duration,
+    intervals: VecDeque<Duration>,
+    last_heartbeat: Instant,
+}
+
+impl PhiAccrualSuspicion {
+    pub fn new(
+        threshold: f64,
+        window_size: usize,
+        min_std_deviation: Duration,
+        first_heartbeat_estimate: Duration,
+        now: Instant,
+    ) -> Self {
+        // Bootstrap the distribution, so that phi is meaningful before the first heartbeats.
+        let deviation = first_heartbeat_estimate / 4;
+        Self {
+            threshold,
+            window_size: window_size.max(2),
+            min_std_deviation,
+            intervals: VecDeque::from([
+                first_heartbeat_estimate - deviation,
+                first_heartbeat_estimate + deviation,
+            ]),
+            last_heartbeat: now,
+        }
+    }
+
+    fn phi(&self, now: Instant) -> f64 {
+        let count = self.intervals.len() as f64;
+        let mean = self
+            .intervals
+            .iter()
+            .map(Duration::as_secs_f64)
+            .sum::<f64>()
+            / count;
+        let variance = self
+            .intervals
+            .iter()
+            .map(|interval| (interval.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let std_deviation = variance.sqrt().max(self.min_std_deviation.as_secs_f64());
+        phi(
+            now.duration_since(self.last_heartbeat).as_secs_f64(),
+            mean,
+            std_deviation,
+        )
+    }
+}
+
+impl Suspicion for PhiAccrualSuspicion {
+    fn heartbeat(&mut self, now: Instant) {
+        if self.intervals.len() >= self.window_size {
+            self.intervals.pop_front();
+        }
+        self.intervals
+            .push_back(now.duration_since(self.last_heartbeat));
+        self.last_heartbeat = now;
+    }
+
+    fn status_without_response(&self, now: Instant) -> NodeStatus {
+        if self.phi(now) < self.threshold {
+            NodeStatus::Suspected
+        } else {
+            NodeStatus::Dead
+        }
+    }
+}
+
+/// Computes phi using a logistic approximation of the normal distribution's CDF.
+fn phi(elapsed: f64, mean: f64, std_deviation: f64) -> f64 {
+    let y = (elapsed - mean) / std_deviation;
+    let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+    if elapsed > mean {
+        -(e / (1.0 + e)).log10()
+    } else {
+        -(1.0 - 1.0 / (1.0 + e)).log10()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phi_grows_with_elapsed_time() {
+        let values = [0.5, 1.0, 1.5, 2.0, 3.0].map(|elapsed| phi(elapsed, 1.0, 0.2));
+        assert!(values[1] > 0.2 && values[1] < 0.4);
+        values.windows(2).for_each(|w| assert!(w[0] < w[1]));
+        assert!(values[4] > 8.0);
+    }
+
+    #[test]
+    fn test_status_without_response() {
+        let start = Instant::now();
+        let mut suspicion = PhiAccrualSuspicion::new(
+            8.0,
+            100,
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            start,
+        );
+        (1..=10).for_each(|i| suspicion.heartbeat(start + Duration::from_secs(i)));
+        let last = start + Duration::from_secs(10);
+        assert_eq!(
+            suspicion.status_without_response(last + Duration::from_millis(1200)),
+            NodeStatus::Suspected
+        );
+        assert_eq!(
+            suspicion.status_without_response(last + Duration::from_secs(3)),
+            NodeStatus::Dead
+        );
+    }
+}
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::time::{Duration, Instant};
+
+use crate::failure_detector::{NodeStatus, Suspicion};
+
+/// Considers a node dead once it has not responded for a fixed amount of time.
+pub struct TimeoutSuspicion {
+    dead_after: Duration,
+    last_heartbeat: Instant,
+}
+
+impl TimeoutSuspicion {
+    pub fn new(dead_after: Duration, now: Instant) -> Self {
+        Self {
+            dead_after,
+            last_heartbeat: now,
+        }
+    }
+}
+
+impl Suspicion for TimeoutSuspicion {
+    fn heartbeat(&mut self, now: Instant) {
+        self.last_heartbeat = now;
+    }
+
+    fn status_without_response(&self, now: Instant) -> NodeStatus {
+        if now.duration_since(self.last_heartbeat) < self.dead_after {
+            NodeStatus::Suspected
+        } else {
+            NodeStatus::Dead
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_without_response() {
+        let start = Instant::now();
+        let suspicion = TimeoutSuspicion::new(Duration::from_secs(5), start);
+        assert_eq!(
+            suspicion.status_without_response(start + Duration::from_secs(4)),
+            NodeStatus::Suspected
+        );
+        assert_eq!(
+            suspicion.status_without_response(start + Duration::from_secs(5)),
+            NodeStatus::Dead
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_resets_the_timeout() {
+        let start = Instant::now();
+        let mut suspicion = TimeoutSuspicion::new(Duration::from_secs(5), start);
+        suspicion.heartbeat(start + Duration::from_secs(4));
+        assert_eq!(
+            suspicion.status_without_response(start + Duration::from_secs(8)),
+            NodeStatus::Suspected
+        );
+        assert_eq!(
+            suspicion.status_without_response(start + Duration::from_secs(9)),
+            NodeStatus::Dead
+        );
+    }
+}
@@ -13,20 +13,42 @@ use async_trait::async_trait;
 use log::info;
 use shaku::{Component, Interface};
 use tokio_util::sync::CancellationToken;
+use tonic::codegen::{BoxStream, StdError};
 use tonic::{Request, Response, Status};
+use tonic::transport::server::TcpIncoming;
 use tonic::transport::{Error, Server};
 
+use crate::admin_grpc_service::AdminGrpcServiceComponent;
 use crate::api::com::barmetler::chord::{
-    FindSuccessorRequest, FindSuccessorResponse, GetPredecessorRequest, GetPredecessorResponse,
+    AddNodeRequest, AddNodeResponse, DeleteRequest, DeleteResponse, DropReplicasRequest,
+    DropReplicasResponse, FindSuccessorRequest, FindSuccessorResponse, GetNodesRequest,
+    GetNodesResponse, GetOwnershipRequest, GetOwnershipResponse, GetPredecessorRequest,
+    GetPredecessorResponse, GetPredecessorsRequest, GetPredecessorsResponse, GetReplicaRequest,
+    GetReplicaResponse, GetRequest, GetResponse, GetRingParametersRequest,
+    GetRingParametersResponse, GetSuccessorsRequest, GetSuccessorsResponse, ListNodesRequest,
+    ListNodesResponse, LookupKeyRequest, LookupKeyResponse, NotifyRequest, NotifyResponse,
+    PingRequest, PingResponse, PredecessorLeavingRequest, PredecessorLeavingResponse, PutRequest,
+    PutResponse, RebalanceRequest, RebalanceResponse, RemoveNodeRequest, RemoveNodeResponse,
+    ReplicateRequest, ReplicateResponse, SuccessorLeavingRequest, SuccessorLeavingResponse,
+    TransferRangeRequest, TransferRangeResponse,
 };
+use crate::api::com::barmetler::chord::admin_service_server::{AdminService, AdminServiceServer};
 use crate::api::com::barmetler::chord::node_service_server::{NodeService, NodeServiceServer};
 use crate::node_grpc_service::NodeGrpcServiceComponent;
 
+/// Listens on `socket_addr` right away, so that requests are accepted as soon as other nodes may
+/// learn about this process, and bind errors surface before the server runs.
+pub fn bind(socket_addr: SocketAddr) -> Result<TcpIncoming, StdError> {
+    TcpIncoming::new(socket_addr, true, None)
+}
+
 #[async_trait]
 pub trait GrpcServer: Interface {
+    /// Serves the node and admin services on `incoming`, which listens on `socket_addr`.
     async fn run(
         &self,
         socket_addr: SocketAddr,
+        incoming: TcpIncoming,
         shutdown: CancellationToken,
     ) -> Result<(), tonic::transport::Error>;
 }
@@ -36,16 +58,27 @@ pub trait GrpcServer: Interface {
 pub struct GrpcServerImpl {
     #[shaku(inject)]
     node_grpc_service: Arc<dyn NodeGrpcServiceComponent>,
+
+    #[shaku(inject)]
+    admin_grpc_service: Arc<dyn AdminGrpcServiceComponent>,
 }
 
 #[async_trait]
 impl GrpcServer for GrpcServerImpl {
-    async fn run(&self, socket_addr: SocketAddr, shutdown: CancellationToken) -> Result<(), Error> {
+    async fn run(
+        &self,
+        socket_addr: SocketAddr,
+        incoming: TcpIncoming,
+        shutdown: CancellationToken,
+    ) -> Result<(), Error> {
         Server::builder()
             .add_service(NodeServiceServer::new(NodeServiceWrapper(
                 self.node_grpc_service.clone(),
             )))
-            .serve_with_shutdown(socket_addr, async {
+            .add_service(AdminServiceServer::new(AdminServiceWrapper(
+                self.admin_grpc_service.clone(),
+            )))
+            .serve_with_incoming_shutdown(incoming, async {
                 info!("Server started on {}", socket_addr);
                 shutdown.cancelled().await;
                 info!("Shutting down server on {}...", socket_addr);
@@ -71,4 +104,147 @@ impl NodeService for NodeServiceWrapper {
     ) -> Result<Response<GetPredecessorResponse>, Status> {
         self.0.get_predecessor(request).await
     }
+
+    async fn get_successors(
+        &self,
+        request: Request<GetSuccessorsRequest>,
+    ) -> Result<Response<GetSuccessorsResponse>, Status> {
+        self.0.get_successors(request).await
+    }
+
+    async fn get_predecessors(
+        &self,
+        request: Request<GetPredecessorsRequest>,
+    ) -> Result<Response<GetPredecessorsResponse>, Status> {
+        self.0.get_predecessors(request).await
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        self.0.ping(request).await
+    }
+
+    async fn get_ring_parameters(
+        &self,
+        request: Request<GetRingParametersRequest>,
+    ) -> Result<Response<GetRingParametersResponse>, Status> {
+        self.0.get_ring_parameters(request).await
+    }
+
+    async fn notify(
+        &self,
+        request: Request<NotifyRequest>,
+    ) -> Result<Response<NotifyResponse>, Status> {
+        self.0.notify(request).await
+    }
+
+    async fn successor_leaving(
+        &self,
+        request: Request<SuccessorLeavingRequest>,
+    ) -> Result<Response<SuccessorLeavingResponse>, Status> {
+        self.0.successor_leaving(request).await
+    }
+
+    async fn predecessor_leaving(
+        &self,
+        request: Request<PredecessorLeavingRequest>,
+    ) -> Result<Response<PredecessorLeavingResponse>, Status> {
+        self.0.predecessor_leaving(request).await
+    }
+
+    async fn get_nodes(
+        &self,
+        request: Request<GetNodesRequest>,
+    ) -> Result<Response<GetNodesResponse>, Status> {
+        self.0.get_nodes(request).await
+    }
+
+    async fn lookup_key(
+        &self,
+        request: Request<LookupKeyRequest>,
+    ) -> Result<Response<LookupKeyResponse>, Status> {
+        self.0.lookup_key(request).await
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        self.0.get(request).await
+    }
+
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>, Status> {
+        self.0.put(request).await
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        self.0.delete(request).await
+    }
+
+    async fn get_replica(
+        &self,
+        request: Request<GetReplicaRequest>,
+    ) -> Result<Response<GetReplicaResponse>, Status> {
+        self.0.get_replica(request).await
+    }
+
+    async fn replicate(
+        &self,
+        request: Request<ReplicateRequest>,
+    ) -> Result<Response<ReplicateResponse>, Status> {
+        self.0.replicate(request).await
+    }
+
+    async fn drop_replicas(
+        &self,
+        request: Request<DropReplicasRequest>,
+    ) -> Result<Response<DropReplicasResponse>, Status> {
+        self.0.drop_replicas(request).await
+    }
+
+    async fn transfer_range(
+        &self,
+        request: Request<TransferRangeRequest>,
+    ) -> Result<Response<BoxStream<TransferRangeResponse>>, Status> {
+        self.0.transfer_range(request).await
+    }
+}
+
+struct AdminServiceWrapper(Arc<dyn AdminService>);
+
+#[async_trait]
+impl AdminService for AdminServiceWrapper {
+    async fn add_node(
+        &self,
+        request: Request<AddNodeRequest>,
+    ) -> Result<Response<AddNodeResponse>, Status> {
+        self.0.add_node(request).await
+    }
+
+    async fn remove_node(
+        &self,
+        request: Request<RemoveNodeRequest>,
+    ) -> Result<Response<RemoveNodeResponse>, Status> {
+        self.0.remove_node(request).await
+    }
+
+    async fn list_nodes(
+        &self,
+        request: Request<ListNodesRequest>,
+    ) -> Result<Response<ListNodesResponse>, Status> {
+        self.0.list_nodes(request).await
+    }
+
+    async fn get_ownership(
+        &self,
+        request: Request<GetOwnershipRequest>,
+    ) -> Result<Response<GetOwnershipResponse>, Status> {
+        self.0.get_ownership(request).await
+    }
+
+    async fn rebalance(
+        &self,
+        request: Request<RebalanceRequest>,
+    ) -> Result<Response<RebalanceResponse>, Status> {
+        self.0.rebalance(request).await
+    }
 }
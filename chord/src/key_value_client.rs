@@ -0,0 +1,598 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use shaku::{module, Component, HasComponent, Interface};
+use tonic::Status;
+
+use chord_types::chord_id::ChordId;
+use chord_types::node_info::NodeInfo;
+
+use crate::args::{KeyValueArgs, KeyValueCommand};
+use crate::config::{
+    Config, ConfigProvider, Consistency, DefaultConfigProvider, DefaultConfigProviderParameters,
+};
+use crate::failure_detector::{FailureDetector, NodeStatus, PingFailureDetector};
+use crate::lookup_driver::{DefaultLookupDriver, LookupDriver, LookupResult};
+use crate::node::{DynNode, NodeError, RingParameters};
+use crate::node_client_factory::{GrpcNodeClientFactory, NodeClientFactory};
+use crate::node_grpc_service::NodeServiceError;
+use crate::node_registry::DefaultNodeRegistry;
+use crate::replication::{required_nodes, resolve_read, select_replicas};
+
+/// Reads and writes application keys on the nodes responsible for them.
+///
+/// Every operation hashes the key, finds its successor with a lookup starting at `start`, and
+/// performs the operation on that node, which coordinates it with its replicas as `consistency`
+/// requires. Reads fall back to the replicas of the key if that node fails to respond, and return
+/// the newest of their copies once as many of them as `consistency` requires responded.
+#[async_trait]
+pub trait KeyValueClient: Interface {
+    async fn get(
+        &self,
+        start: &DynNode,
+        key: &[u8],
+        consistency: Option<Consistency>,
+    ) -> Result<Option<Vec<u8>>, NodeError>;
+
+    /// Fails if too few nodes acknowledged the write, which may still be partly applied, see
+    /// [Node::put](crate::node::Node::put).
+    async fn put(
+        &self,
+        start: &DynNode,
+        key: &[u8],
+        value: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<(), NodeError>;
+
+    /// Removes `key`, returning whether it was stored. Fails like [KeyValueClient::put].
+    async fn delete(&self, start: &DynNode, key: &[u8]) -> Result<bool, NodeError>;
+}
+
+#[derive(Component)]
+#[shaku(interface = KeyValueClient)]
+pub struct DefaultKeyValueClient {
+    #[shaku(inject)]
+    lookup_driver: Arc<dyn LookupDriver>,
+
+    #[shaku(inject)]
+    client_factory: Arc<dyn NodeClientFactory>,
+
+    #[shaku(inject)]
+    failure_detector: Arc<dyn FailureDetector>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+impl DefaultKeyValueClient {
+    async fn find_owner(&self, start: &DynNode, key: &[u8]) -> Result<Arc<DynNode>, NodeError> {
+        let owner = self.lookup_driver.lookup_key(start, key, true).await?;
+        Ok(self.client_factory.create_node_client(&owner.successor))
+    }
+
+    /// Finds the nodes replicating the entries of `owner`, without contacting `owner` itself.
+    async fn find_replicas(
+        &self,
+        start: &DynNode,
+        owner: &NodeInfo,
+    ) -> Result<Vec<NodeInfo>, NodeError> {
+        let count = self
+            .config_provider
+            .get_config()
+            .replication_config
+            .replication_factor
+            .saturating_sub(1);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let next = self
+            .lookup_driver
+            .find_successor(start, owner.id + ChordId::from(1u128), true)
+            .await?
+            .successor;
+        let mut successors = vec![next.clone()];
+        match self.client_factory.create_node_client(&next).get_successors().await {
+            Ok(rest) => successors.extend(rest),
+            Err(e) => log::debug!("failed to get successors of {}: {}", next.id, e),
+        }
+        Ok(select_replicas(owner, successors, count))
+    }
+}
+
+#[async_trait]
+impl KeyValueClient for DefaultKeyValueClient {
+    async fn get(
+        &self,
+        start: &DynNode,
+        key: &[u8],
+        consistency: Option<Consistency>,
+    ) -> Result<Option<Vec<u8>>, NodeError> {
+        let owner = self.find_owner(start, key).await?;
+        let owner_info = owner.node_info();
+        if self.failure_detector.check_node(&owner_info).await == NodeStatus::Alive {
+            match owner.get(key.to_vec(), consistency).await {
+                Ok(value) => return Ok(value),
+                Err(e) => log::debug!("failed to read from owner {}: {}", owner_info.id, e),
+            }
+        }
+        // A replica would coordinate the read over its own replicas, so its copy is read directly
+        let config = self.config_provider.get_config();
+        let required = required_nodes(
+            consistency.unwrap_or(config.consistency_config.read),
+            config.replication_config.replication_factor,
+        )?;
+        let mut copies = Vec::new();
+        for replica in self.find_replicas(start, &owner_info).await? {
+            if copies.len() == required {
+                break;
+            }
+            match self
+                .client_factory
+                .create_node_client(&replica)
+                .get_replica(key.to_vec())
+                .await
+            {
+                Ok(copy) => copies.push(copy),
+                Err(e) => log::debug!("failed to read from replica {}: {}", replica.id, e),
+            }
+        }
+        if copies.len() < required {
+            return Err(NodeError::quorum_not_reached(required, copies.len()));
+        }
+        Ok(resolve_read(copies))
+    }
+
+    async fn put(
+        &self,
+        start: &DynNode,
+        key: &[u8],
+        value: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<(), NodeError> {
+        self.find_owner(start, key)
+            .await?
+            .put(key.to_vec(), value, consistency)
+            .await
+    }
+
+    async fn delete(&self, start: &DynNode, key: &[u8]) -> Result<bool, NodeError> {
+        self.find_owner(start, key)
+            .await?
+            .delete(key.to_vec())
+            .await
+    }
+}
+
+/// Runs a key-value command against the ring the process at `args.target` is part of, and prints
+/// the result.
+///
+/// Reads and writes with `consistency`, or the default level of the owner of the key. Fails if the
+/// ring does not use the hash algorithm of `config`, since the keys would be looked up in the wrong
+/// place otherwise.
+pub async fn run_key_value_command(
+    args: KeyValueArgs,
+    config: Config,
+    consistency: Option<Consistency>,
+) -> Result<(), Status> {
+    let local = RingParameters {
+        id_bits: ChordId::BITS as u32,
+        hash_algorithm: config.hash_algorithm,
+    };
+    let program = ClientProgram::builder()
+        .with_component_parameters::<DefaultConfigProvider>(DefaultConfigProviderParameters {
+            config: Arc::new(config),
+        })
+        .build();
+    let client_factory: Arc<dyn NodeClientFactory> = program.resolve();
+    let client: Arc<dyn KeyValueClient> = program.resolve();
+
+    let hosted = client_factory
+        .get_hosted_nodes(args.target)
+        .await
+        .map_err(NodeServiceError::from)?;
+    let start = hosted
+        .nodes
+        .first()
+        .map(|node| client_factory.create_node_client(node))
+        .ok_or_else(|| Status::unavailable(format!("{} hosts no nodes", args.target)))?;
+    let remote = start
+        .get_ring_parameters()
+        .await
+        .map_err(NodeServiceError::from)?;
+    if local != remote {
+        let error = NodeError::incompatible_ring(local, remote);
+        return Err(NodeServiceError::from(error).into());
+    }
+
+    match args.command {
+        KeyValueCommand::Lookup { key } => {
+            let lookup_driver: Arc<dyn LookupDriver> = program.resolve();
+            let LookupResult { successor, hops } = lookup_driver
+                .lookup_key(&*start, key.as_bytes(), true)
+                .await
+                .map_err(NodeServiceError::from)?;
+            let endpoints: Vec<_> = successor
+                .endpoints
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            println!("{} {} ({} hops)", successor.id, endpoints.join(","), hops);
+        }
+        KeyValueCommand::Get { key, .. } => {
+            let value = client
+                .get(&*start, key.as_bytes(), consistency)
+                .await
+                .map_err(NodeServiceError::from)?
+                .ok_or_else(|| Status::not_found(format!("key {} is not stored", key)))?;
+            println!("{}", String::from_utf8_lossy(&value));
+        }
+        KeyValueCommand::Put { key, value, .. } => {
+            client
+                .put(&*start, key.as_bytes(), value.into_bytes(), consistency)
+                .await
+                .map_err(NodeServiceError::from)?;
+        }
+        KeyValueCommand::Delete { key } => {
+            let deleted = client
+                .delete(&*start, key.as_bytes())
+                .await
+                .map_err(NodeServiceError::from)?;
+            if !deleted {
+                return Err(Status::not_found(format!("key {} is not stored", key)));
+            }
+        }
+    }
+    Ok(())
+}
+
+module! {
+    ClientProgram {
+        components = [
+            DefaultConfigProvider,
+            DefaultKeyValueClient,
+            DefaultLookupDriver,
+            DefaultNodeRegistry,
+            GrpcNodeClientFactory,
+            PingFailureDetector,
+        ],
+        providers = []
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use shaku::HasComponent;
+
+    use crate::config::{Config, ConsistencyConfig, ReplicationConfig};
+    use crate::node::DynLocalNode;
+    use crate::node_manager::NodeManager;
+    use crate::replication::Replica;
+    use crate::testing::TestNetwork;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_operations_reach_the_owner() {
+        let network = TestNetwork::new();
+        let program = network.create_process(Config::default());
+        let node_manager: Arc<dyn NodeManager> = program.resolve();
+        let client: Arc<dyn KeyValueClient> = program.resolve();
+        for i in 0..5u128 {
+            let id = ChordId::from(i * (u128::MAX / 5));
+            node_manager.add_node(Some(id)).await.unwrap();
+            stabilize(&node_manager.list_nodes()).await;
+        }
+        let mut nodes = node_manager.list_nodes();
+        nodes.sort_by_key(|node| node.id());
+
+        let keys: Vec<_> = (0..20).map(|i| format!("key-{}", i).into_bytes()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            let start: &DynNode = &*nodes[i % nodes.len()];
+            client.put(start, key, key.clone(), None).await.unwrap();
+        }
+        for key in &keys {
+            let owner = nodes[0].lookup_key(key).await.unwrap().successor;
+            for node in &nodes {
+                let stored = node.get(key.clone(), None).await.unwrap();
+                if node.id() == owner.id {
+                    assert_eq!(stored.as_ref(), Some(key));
+                } else {
+                    assert_eq!(stored, None);
+                }
+            }
+            let start: &DynNode = &*nodes[3];
+            assert_eq!(
+                client.get(start, key, None).await.unwrap().as_ref(),
+                Some(key)
+            );
+        }
+
+        let start: &DynNode = &*nodes[1];
+        assert!(client.delete(start, &keys[0]).await.unwrap());
+        assert!(!client.delete(start, &keys[0]).await.unwrap());
+        assert_eq!(client.get(start, &keys[0], None).await.unwrap(), None);
+    }
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    }
+
+    /// The processes whose live nodes store `key`.
+    async fn holders(
+        network: &TestNetwork,
+        nodes: &[Arc<DynLocalNode>],
+        key: &[u8],
+    ) -> HashSet<String> {
+        let mut processes = HashSet::new();
+        for node in nodes.iter().filter(|node| network.is_alive(node.id())) {
+            if node.get(key.to_vec(), None).await.unwrap().is_some() {
+                processes.insert(node.node_info().process());
+            }
+        }
+        processes
+    }
+
+    /// Starts `count` processes with two interleaved nodes each, which store every key three times,
+    /// and returns the client of the first process.
+    async fn create_processes(
+        network: &Arc<TestNetwork>,
+        count: u16,
+        config: Config,
+    ) -> (Arc<dyn KeyValueClient>, Vec<Arc<DynLocalNode>>) {
+        let mut clients = Vec::new();
+        let mut nodes = Vec::new();
+        for port in 1..=count {
+            let step = u128::MAX / (2 * count as u128);
+            let ids: Vec<_> = (0..2)
+                .map(|index| ChordId::from((port as u128 + count as u128 * index) * step))
+                .collect();
+            clients.push(start_process(network, port, &ids, config.clone(), &mut nodes).await);
+        }
+        (clients.swap_remove(0), nodes)
+    }
+
+    /// Starts a process with nodes at `ids`, which joins the process on port 1, and adds its nodes
+    /// to `nodes`.
+    async fn start_process(
+        network: &Arc<TestNetwork>,
+        port: u16,
+        ids: &[ChordId],
+        config: Config,
+        nodes: &mut Vec<Arc<DynLocalNode>>,
+    ) -> Arc<dyn KeyValueClient> {
+        let program = network.create_process(Config {
+            advertised_address: Some(address(port)),
+            join_addresses: if port > 1 { vec![address(1)] } else { vec![] },
+            replication_config: ReplicationConfig {
+                replication_factor: 3,
+                ..Default::default()
+            },
+            ..config
+        });
+        let node_manager: Arc<dyn NodeManager> = program.resolve();
+        for &id in ids {
+            nodes.push(node_manager.add_node(Some(id)).await.unwrap());
+            stabilize(nodes).await;
+        }
+        program.resolve()
+    }
+
+    async fn stabilize(nodes: &[Arc<DynLocalNode>]) {
+        for _ in 0..4 {
+            for node in nodes {
+                node.stabilize().await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replicas_survive_process_failure() {
+        let network = TestNetwork::new();
+        // Wait for all replicas, so that they can be counted right after each write
+        let (client, nodes) = create_processes(
+            &network,
+            4,
+            Config {
+                consistency_config: ConsistencyConfig {
+                    write: Consistency::All,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await;
+        stabilize(&nodes).await;
+
+        let keys: Vec<_> = (0..20).map(|i| format!("key-{}", i).into_bytes()).collect();
+        for key in &keys {
+            client
+                .put(&*nodes[0], key, key.clone(), None)
+                .await
+                .unwrap();
+            assert_eq!(holders(&network, &nodes, key).await.len(), 3);
+        }
+
+        // The second process crashes
+        for node in &nodes {
+            if node.node_info().process() == nodes[2].node_info().process() {
+                network.kill(node.id());
+            }
+        }
+        for key in &keys {
+            assert_eq!(
+                client.get(&*nodes[0], key, None).await.unwrap().as_ref(),
+                Some(key)
+            );
+        }
+
+        // The remaining processes re-create the lost replicas
+        let survivors: Vec<_> = nodes
+            .iter()
+            .filter(|node| network.is_alive(node.id()))
+            .cloned()
+            .collect();
+        stabilize(&survivors).await;
+        for key in &keys {
+            assert_eq!(holders(&network, &nodes, key).await.len(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consistency_levels() {
+        let network = TestNetwork::new();
+        let (client, nodes) = create_processes(&network, 3, Config::default()).await;
+        stabilize(&nodes).await;
+
+        let key = b"key".to_vec();
+        client
+            .put(&*nodes[0], &key, b"a".to_vec(), Some(Consistency::All))
+            .await
+            .unwrap();
+        assert_eq!(holders(&network, &nodes, &key).await.len(), 3);
+        let error = client
+            .put(&*nodes[0], &key, b"b".to_vec(), Some(Consistency::Count(4)))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, NodeError::InvalidConsistency { .. }));
+
+        // Kill the process of a replica, so that only two of the three nodes storing the key remain
+        let owner = nodes[0].lookup_key(&key).await.unwrap().successor;
+        let replica = nodes
+            .iter()
+            .find(|node| node.node_info().process() != owner.process())
+            .unwrap()
+            .node_info();
+        for node in &nodes {
+            if node.node_info().process() == replica.process() {
+                network.kill(node.id());
+            }
+        }
+        let start = nodes
+            .iter()
+            .find(|node| network.is_alive(node.id()))
+            .unwrap();
+
+        let error = client
+            .put(&**start, &key, b"b".to_vec(), Some(Consistency::All))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            NodeError::QuorumNotReached {
+                required: 3,
+                reached: 2
+            }
+        ));
+        client
+            .put(&**start, &key, b"c".to_vec(), Some(Consistency::Quorum))
+            .await
+            .unwrap();
+        let value = client
+            .get(&**start, &key, Some(Consistency::Quorum))
+            .await
+            .unwrap();
+        assert_eq!(value, Some(b"c".to_vec()));
+        let error = client
+            .get(&**start, &key, Some(Consistency::All))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, NodeError::QuorumNotReached { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_reads_return_the_newest_copy() {
+        let network = TestNetwork::new();
+        let (client, nodes) = create_processes(&network, 3, Config::default()).await;
+        stabilize(&nodes).await;
+
+        let key = b"key".to_vec();
+        client
+            .put(&*nodes[0], &key, b"a".to_vec(), Some(Consistency::All))
+            .await
+            .unwrap();
+        let owner = nodes[0].lookup_key(&key).await.unwrap().successor;
+        let mut copies = Vec::new();
+        for node in &nodes {
+            if let Some(copy) = node.get_replica(key.clone()).await.unwrap() {
+                copies.push((node.clone(), copy));
+            }
+        }
+        assert_eq!(copies.len(), 3);
+        let version = copies[0].1.version;
+        let replicas: Vec<_> = copies
+            .iter()
+            .map(|(node, _)| node)
+            .filter(|node| node.id() != owner.id)
+            .collect();
+
+        // Only one of the three nodes stores the newest value
+        replicas[0]
+            .replicate(vec![Replica::put(key.clone(), b"b".to_vec(), version + 1)])
+            .await
+            .unwrap();
+        let value = client
+            .get(&*nodes[0], &key, Some(Consistency::All))
+            .await
+            .unwrap();
+        assert_eq!(value, Some(b"b".to_vec()));
+
+        // Older copies are ignored, and deletions replace older values
+        replicas[1]
+            .replicate(vec![Replica::put(key.clone(), b"c".to_vec(), version - 1)])
+            .await
+            .unwrap();
+        replicas[1]
+            .replicate(vec![Replica::delete(key.clone(), version + 2)])
+            .await
+            .unwrap();
+        let value = client
+            .get(&*nodes[0], &key, Some(Consistency::All))
+            .await
+            .unwrap();
+        assert_eq!(value, None);
+    }
+
+    #[tokio::test]
+    async fn test_joining_replicas_replace_old_copies() {
+        let network = TestNetwork::new();
+        let config = Config {
+            consistency_config: ConsistencyConfig {
+                write: Consistency::All,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (client, mut nodes) = create_processes(&network, 3, config.clone()).await;
+        stabilize(&nodes).await;
+        let keys: Vec<_> = (0..20).map(|i| format!("key-{}", i).into_bytes()).collect();
+        for key in &keys {
+            client
+                .put(&*nodes[0], key, key.clone(), None)
+                .await
+                .unwrap();
+        }
+
+        // The nodes of a new process join right behind existing ones, and replace the farthest
+        // replicas of their predecessors
+        let step = u128::MAX / 6;
+        let ids: Vec<_> = (0..6)
+            .map(|index| ChordId::from(index * step + step / 2))
+            .collect();
+        start_process(&network, 4, &ids, config, &mut nodes).await;
+        stabilize(&nodes).await;
+        for key in &keys {
+            assert_eq!(holders(&network, &nodes, key).await.len(), 3);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use shaku::{Component, Interface};
+
+use chord_types::chord_id::ChordId;
+use chord_types::hashing::KeyHasher;
+use chord_types::node_info::NodeInfo;
+
+use crate::config::ConfigProvider;
+use crate::node::{DynNode, FindSuccessorParameters, FindSuccessorResult, NodeError};
+use crate::node_client_factory::NodeClientFactory;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LookupResult {
+    pub successor: NodeInfo,
+    /// The number of hops it took to find the successor.
+    pub hops: u32,
+}
+
+#[async_trait]
+pub trait LookupDriver: Interface {
+    /// Finds the successor of `id`, starting the lookup at `start`.
+    ///
+    /// If `iterate` is `false`, the lookup is forwarded recursively by the nodes themselves.
+    /// Otherwise, every node only returns the next hop, which is then followed by the driver.
+    async fn find_successor(
+        &self,
+        start: &DynNode,
+        id: ChordId,
+        iterate: bool,
+    ) -> Result<LookupResult, NodeError>;
+
+    /// Finds the node responsible for the application key `key`, which is hashed onto the ring
+    /// with the configured hash algorithm.
+    async fn lookup_key(
+        &self,
+        start: &DynNode,
+        key: &[u8],
+        iterate: bool,
+    ) -> Result<LookupResult, NodeError>;
+}
+
+#[derive(Component)]
+#[shaku(interface = LookupDriver)]
+pub struct DefaultLookupDriver {
+    #[shaku(inject)]
+    client_factory: Arc<dyn NodeClientFactory>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+#[async_trait]
+impl LookupDriver for DefaultLookupDriver {
+    async fn find_successor(
+        &self,
+        start: &DynNode,
+        id: ChordId,
+        iterate: bool,
+    ) -> Result<LookupResult, NodeError> {
+        let max_hops = self.config_provider.get_config().lookup_config.max_hops;
+        let mut outcome = start
+            .find_successor(FindSuccessorParameters {
+                id,
+                iterate,
+                hops: 0,
+            })
+            .await?;
+        loop {
+            let next = match outcome.result {
+                FindSuccessorResult::Successor(successor) => {
+                    return Ok(LookupResult {
+                        successor,
+                        hops: outcome.hops,
+                    })
+                }
+                FindSuccessorResult::ClosestPrecedingNode(next) => next,
+            };
+            if outcome.hops >= max_hops {
+                return Err(NodeError::max_hops_exceeded(id, max_hops));
+            }
+            let node = self.client_factory.create_node_client(&next);
+            outcome = node
+                .find_successor(FindSuccessorParameters {
+                    id,
+                    iterate,
+                    hops: outcome.hops + 1,
+                })
+                .await?;
+        }
+    }
+
+    async fn lookup_key(
+        &self,
+        start: &DynNode,
+        key: &[u8],
+        iterate: bool,
+    ) -> Result<LookupResult, NodeError> {
+        let id = self.config_provider.get_config().hash_algorithm.hash(key);
+        self.find_successor(start, id, iterate).await
+    }
+}
@@ -6,41 +6,76 @@
  * https://opensource.org/licenses/MIT.
  */
 
-#![feature(trait_upcasting)]
+// `NodeError` carries a grpc `Status`, which is large but only built on the error path
+#![allow(clippy::result_large_err)]
 
-use std::collections::HashMap;
-use std::iter::repeat_with;
 use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
-use log::{info, warn};
-use rand::random;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use log::{error, info, warn};
 use shaku::{HasComponent, module};
+use tokio_util::sync::CancellationToken;
 
-use args::Args;
+use chord_types::chord_id::ChordId;
+use chord_types::hashing::HashAlgorithm;
+use chord_types::node_info::Endpoint;
+
+use args::{Args, Command, KeyValueCommand};
 use node_factory::DefaultNodeFactory;
 
-use crate::config::{Config, DefaultConfigProvider, DefaultConfigProviderParameters};
-use crate::interface::grpc_server::{GrpcServer, GrpcServerImpl};
+use crate::admin_client::run_admin_command;
+use crate::admin_grpc_service::AdminGrpcService;
+use crate::config::{
+    Config, Consistency, ConsistencyConfig, DefaultConfigProvider, DefaultConfigProviderParameters,
+    FsyncPolicy, MaintenanceConfig, NodeIdConfig, RebalanceConfig, ReplicationConfig,
+    RoutingCacheConfig, StorageConfig,
+};
+use crate::failure_detector::PingFailureDetector;
+use crate::interface::grpc_server::{bind, GrpcServer, GrpcServerImpl};
+use crate::key_value_client::{run_key_value_command, DefaultKeyValueClient};
 use crate::logging::init_logging;
+use crate::lookup_driver::DefaultLookupDriver;
+use crate::maintenance::DefaultMaintenance;
+use crate::membership::{DefaultMembership, Membership};
+use crate::node::DynLocalNode;
 use crate::node_client_factory::GrpcNodeClientFactory;
-use crate::node_factory::NodeFactory;
+use crate::node_factory::{is_specified, NodeFactory};
 use crate::node_grpc_service::NodeGrpcService;
 use crate::node_manager::{NodeManager, NodeManagerImpl};
+use crate::node_registry::DefaultNodeRegistry;
+use crate::rebalancer::{start_rebalancing, DefaultRebalancer, Rebalancer};
+use crate::replication::{required_neighbors, required_nodes};
+use crate::routing_cache::DefaultRoutingCache;
 use crate::util::shutdown_source::start_shutdown_listener;
 
+mod admin_client;
+mod admin_grpc_service;
 mod api;
 mod args;
 mod config;
 mod convert;
+mod failure_detector;
 mod interface;
+mod key_value_client;
 mod logging;
+mod lookup_driver;
+mod maintenance;
+mod membership;
 mod node;
 mod node_client_factory;
 mod node_factory;
 mod node_grpc_client;
 mod node_grpc_service;
 mod node_manager;
+mod node_registry;
+mod rebalancer;
+mod replication;
+mod routing_cache;
+mod storage;
+#[cfg(test)]
+mod testing;
 mod util;
 
 #[tokio::main]
@@ -49,12 +84,134 @@ async fn main() {
 
     let args = Args::parse();
 
+    let hash_algorithm = match args.hash_algorithm.as_str() {
+        "sha256" => HashAlgorithm::Sha256,
+        "xxhash" => HashAlgorithm::XxHash,
+        _ => HashAlgorithm::Sha1,
+    };
+
+    let consistency_config = ConsistencyConfig {
+        read: parse_consistency(&args.read_consistency, args.replicas),
+        write: parse_consistency(&args.write_consistency, args.replicas),
+    };
+
+    let result = match args.command {
+        Some(Command::Admin(admin_args)) => Some(run_admin_command(admin_args).await),
+        Some(Command::Kv(key_value_args)) => {
+            let consistency = match &key_value_args.command {
+                KeyValueCommand::Get { consistency, .. }
+                | KeyValueCommand::Put { consistency, .. } => consistency.as_deref(),
+                KeyValueCommand::Lookup { .. } | KeyValueCommand::Delete { .. } => None,
+            }
+            .map(|level| parse_consistency(level, args.replicas));
+            let config = Config {
+                hash_algorithm,
+                replication_config: ReplicationConfig {
+                    replication_factor: args.replicas as usize,
+                    ..Default::default()
+                },
+                consistency_config: consistency_config.clone(),
+                ..Default::default()
+            };
+            Some(run_key_value_command(key_value_args, config, consistency).await)
+        }
+        None => None,
+    };
+    if let Some(result) = result {
+        if let Err(e) = result {
+            error!("{}: {}", e.code(), e.message());
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let pinned_ids = args
+        .node_ids
+        .iter()
+        .map(|id| id.parse())
+        .collect::<Result<Vec<ChordId>, _>>()
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::ValueValidation, e).exit());
+
+    let advertised_endpoints = args
+        .advertised_endpoints
+        .iter()
+        .map(|endpoint| endpoint.parse())
+        .collect::<Result<Vec<Endpoint>, _>>()
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::ValueValidation, e).exit());
+
+    if args.capacity.is_nan() || args.capacity <= 0.0 {
+        Args::command()
+            .error(ErrorKind::ValueValidation, "the capacity must be positive")
+            .exit();
+    }
+    let rebalance_interval = args.rebalance_interval.map(Duration::from_secs);
+    let rebalance_config = RebalanceConfig {
+        capacity: args.capacity,
+        interval: rebalance_interval,
+        ..Default::default()
+    };
+
+    let storage_config = StorageConfig {
+        data_dir: args.data_dir,
+        fsync_policy: match args.fsync_policy.as_str() {
+            "always" => FsyncPolicy::Always,
+            "never" => FsyncPolicy::Never,
+            _ => FsyncPolicy::Batched {
+                max_pending: args.fsync_max_pending as usize,
+                max_delay: Duration::from_millis(args.fsync_max_delay),
+            },
+        },
+        ..Default::default()
+    };
+
+    // Ids are derived from the advertised address, which therefore has to identify this host
+    let advertised_address = args
+        .advertised_address
+        .or(args.socket_addresses.first().copied());
+    if let Some(address) = advertised_address.filter(|address| !is_specified(address)) {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "cannot advertise the unspecified address {}, pass --advertise",
+                    address
+                ),
+            )
+            .exit();
+    }
+
+    // The successors have to include nodes of enough other processes to hold the replicas
+    let default_maintenance_config = MaintenanceConfig::default();
+    let maintenance_config = MaintenanceConfig {
+        neighbor_list_length: default_maintenance_config.neighbor_list_length.max(
+            required_neighbors(args.replicas as usize, args.virtual_nodes as usize),
+        ),
+        ..default_maintenance_config
+    };
+
     let cancellation = start_shutdown_listener();
 
     let program = Program::builder()
         .with_component_parameters::<DefaultConfigProvider>(DefaultConfigProviderParameters {
             config: Arc::new(Config {
                 virtual_nodes: args.virtual_nodes,
+                hash_algorithm,
+                node_id_config: NodeIdConfig { pinned_ids },
+                advertised_address,
+                advertised_endpoints,
+                join_addresses: args.join_addresses,
+                rebalance_config,
+                routing_cache_config: RoutingCacheConfig {
+                    enabled: args.shared_routing,
+                    ..Default::default()
+                },
+                storage_config,
+                maintenance_config,
+                replication_config: ReplicationConfig {
+                    replication_factor: args.replicas as usize,
+                    ..Default::default()
+                },
+                consistency_config,
                 ..Default::default()
             }),
         })
@@ -63,45 +220,131 @@ async fn main() {
     let factory: Arc<dyn NodeFactory> = program.resolve();
     let node_manager: Arc<dyn NodeManager> = program.resolve();
 
-    let nodes: HashMap<_, _> = repeat_with(|| factory.create_node(random()))
-        .map(|node| (node.id(), Arc::from(node)))
-        .take(args.virtual_nodes as usize)
-        .collect();
-    node_manager.initialize(nodes);
+    let membership: Arc<dyn Membership> = program.resolve();
+    let rebalancer: Arc<dyn Rebalancer> = program.resolve();
 
-    let mut tasks = Vec::new();
+    let nodes: Vec<Arc<DynLocalNode>> = (0..args.virtual_nodes)
+        .map(|index| factory.create_virtual_node(index).map(Arc::from))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| {
+            error!("Failed to create the virtual nodes: {}", e);
+            std::process::exit(1);
+        });
+    node_manager.initialize(nodes.iter().map(|node| (node.id(), node.clone())).collect());
+
+    // The servers keep running after the shutdown signal until the nodes have left the ring
+    let server_cancellation = CancellationToken::new();
 
     // Start grpc interfaces
     let socket_addresses = args.socket_addresses;
     if socket_addresses.is_empty() {
         warn!("No socket addresses provided, not starting grpc interfaces");
     }
-    tasks.extend(socket_addresses.into_iter().map(|address| {
-        let grpc_server: Arc<dyn GrpcServer> = program.resolve();
-        let cancellation = cancellation.clone();
-        tokio::spawn(async move {
-            grpc_server.run(address, cancellation).await.unwrap();
+    // Bind all addresses before joining, so that the nodes can be reached once others know them
+    let listeners: Vec<_> = socket_addresses
+        .into_iter()
+        .map(|address| match bind(address) {
+            Ok(incoming) => (address, incoming),
+            Err(e) => {
+                error!("Failed to bind to {}: {}", address, e);
+                std::process::exit(1);
+            }
         })
-    }));
+        .collect();
+    let servers: Vec<_> = listeners
+        .into_iter()
+        .map(|(address, incoming)| {
+            let grpc_server: Arc<dyn GrpcServer> = program.resolve();
+            let server_cancellation = server_cancellation.clone();
+            let cancellation = cancellation.clone();
+            tokio::spawn(async move {
+                if let Err(e) = grpc_server
+                    .run(address, incoming, server_cancellation)
+                    .await
+                {
+                    error!("Server on {} failed: {}", address, e);
+                    cancellation.cancel();
+                }
+            })
+        })
+        .collect();
+
+    // Join the ring once the grpc interfaces are listening, then keep it stable
+    let joined = match membership.join_ring(&nodes).await {
+        Ok(()) => {
+            node_manager.start_maintenance(cancellation.clone());
+            true
+        }
+        Err(e) => {
+            error!("Failed to join the ring: {}", e);
+            cancellation.cancel();
+            false
+        }
+    };
+    let rebalancing = rebalance_interval
+        .filter(|_| joined)
+        .map(|interval| start_rebalancing(rebalancer, interval, cancellation.clone()));
 
     // TODO: start interfaces to communicate with local clients (ethernet, pipes, stdin/stdout, etc.)
 
-    for task in tasks {
-        task.await.unwrap();
+    cancellation.cancelled().await;
+    if let Some(rebalancing) = rebalancing {
+        rebalancing.await.unwrap();
+    }
+    if joined {
+        node_manager.join_maintenance().await;
+        // Nodes may have been added or removed at runtime
+        membership.leave_ring(&node_manager.list_nodes()).await;
+    }
+    server_cancellation.cancel();
+
+    for server in servers {
+        server.await.unwrap();
     }
 
     info!("All servers shut down.");
 }
 
+/// Parses a consistency level given on the command line, and exits if it does not fit the number
+/// of replicas.
+fn parse_consistency(level: &str, replicas: u32) -> Consistency {
+    let consistency = match level {
+        "one" => Consistency::One,
+        "quorum" => Consistency::Quorum,
+        "all" => Consistency::All,
+        count => Consistency::Count(count.parse().unwrap_or_else(|_| {
+            Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("invalid consistency level: {}", level),
+                )
+                .exit()
+        })),
+    };
+    if let Err(e) = required_nodes(consistency, replicas as usize) {
+        Args::command().error(ErrorKind::ValueValidation, e).exit();
+    }
+    consistency
+}
+
 module! {
     Program {
         components = [
+            AdminGrpcService,
             DefaultConfigProvider,
+            DefaultKeyValueClient,
+            DefaultLookupDriver,
+            DefaultMaintenance,
+            DefaultMembership,
             DefaultNodeFactory,
+            DefaultNodeRegistry,
+            DefaultRebalancer,
+            DefaultRoutingCache,
             GrpcNodeClientFactory,
             GrpcServerImpl,
             NodeGrpcService,
             NodeManagerImpl,
+            PingFailureDetector,
         ],
         providers = []
     }
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::sync::Arc;
+
+use log::{debug, warn};
+use rand::{thread_rng, Rng};
+use shaku::{Component, Interface};
+use tokio::select;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{ConfigProvider, FixFingersStrategy};
+use crate::node::DynLocalNode;
+
+pub trait Maintenance: Interface {
+    /// Spawns the periodic maintenance of `node`, which runs until `cancellation` is triggered.
+    fn start(&self, node: Arc<DynLocalNode>, cancellation: CancellationToken) -> JoinHandle<()>;
+}
+
+#[derive(Component)]
+#[shaku(interface = Maintenance)]
+pub struct DefaultMaintenance {
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+impl Maintenance for DefaultMaintenance {
+    fn start(&self, node: Arc<DynLocalNode>, cancellation: CancellationToken) -> JoinHandle<()> {
+        let config = self.config_provider.get_config().maintenance_config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = node.fill_fingers().await {
+                warn!("Failed to fill fingers of node {}: {}", node.id(), e);
+            }
+            let finger_count = node.get_finger_stats().await.size;
+            let mut next_finger = 0;
+            let mut stabilize = interval(config.stabilize_interval);
+            stabilize.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut fix_fingers = interval(config.fix_fingers_interval);
+            fix_fingers.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                select! {
+                    _ = cancellation.cancelled() => break,
+                    _ = stabilize.tick() => {
+                        if let Err(e) = node.stabilize().await {
+                            warn!("Failed to stabilize node {}: {}", node.id(), e);
+                        }
+                    }
+                    _ = fix_fingers.tick(), if finger_count > 0 => {
+                        let index = match config.fix_fingers_strategy {
+                            FixFingersStrategy::RoundRobin => next_finger,
+                            FixFingersStrategy::Random => thread_rng().gen_range(0..finger_count),
+                        };
+                        if let Err(e) = node.fix_finger(index).await {
+                            debug!("Failed to fix finger {} of node {}: {}", index, node.id(), e);
+                        }
+                        next_finger = (next_finger + 1) % finger_count;
+                        if next_finger == 0 {
+                            let stats = node.get_finger_stats().await;
+                            debug!(
+                                "Node {} has {}/{} fingers, max age {:?}, mean age {:?}",
+                                node.id(),
+                                stats.populated,
+                                stats.size,
+                                stats.max_age,
+                                stats.mean_age,
+                            );
+                        }
+                    }
+                }
+            }
+            debug!("Stopped maintenance of node {}", node.id());
+        })
+    }
+}
@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::{info, warn};
+use shaku::{Component, Interface};
+use tokio::time::timeout;
+
+use crate::config::ConfigProvider;
+use crate::node::{DynLocalNode, DynNode, NodeError};
+use crate::node_client_factory::NodeClientFactory;
+
+#[async_trait]
+pub trait Membership: Interface {
+    /// Makes the given local nodes part of a ring.
+    ///
+    /// The first node joins the ring through the configured join addresses, or creates a new ring
+    /// if there are none. All other nodes join through the first one.
+    async fn join_ring(&self, nodes: &[Arc<DynLocalNode>]) -> Result<(), NodeError>;
+
+    /// Makes a single local node part of the ring, given the local nodes that are already members.
+    ///
+    /// The node joins through the first member, or like the first node in [`Membership::join_ring`]
+    /// if there are no members.
+    async fn join_node(
+        &self,
+        node: &DynLocalNode,
+        members: &[Arc<DynLocalNode>],
+    ) -> Result<(), NodeError>;
+
+    /// Makes the given local nodes leave the ring one after another, giving up once the drain
+    /// deadline has passed.
+    async fn leave_ring(&self, nodes: &[Arc<DynLocalNode>]);
+}
+
+#[derive(Component)]
+#[shaku(interface = Membership)]
+pub struct DefaultMembership {
+    #[shaku(inject)]
+    client_factory: Arc<dyn NodeClientFactory>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+impl DefaultMembership {
+    async fn join_through_seeds(&self, node: &DynLocalNode) -> Result<(), NodeError> {
+        let config = self.config_provider.get_config();
+        let mut last_error = NodeError::unknown();
+        for &address in &config.join_addresses {
+            let seed = match self.client_factory.get_hosted_nodes(address).await {
+                Ok(hosted) => match hosted.nodes.first() {
+                    Some(seed) => self.client_factory.create_node_client(seed),
+                    None => {
+                        warn!("{} does not host any nodes", address);
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    warn!("Failed to contact {}: {}", address, e);
+                    last_error = e;
+                    continue;
+                }
+            };
+            match node.join(&*seed).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    warn!("Failed to join through {}: {}", address, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[async_trait]
+impl Membership for DefaultMembership {
+    async fn join_ring(&self, nodes: &[Arc<DynLocalNode>]) -> Result<(), NodeError> {
+        let Some((first, rest)) = nodes.split_first() else {
+            return Ok(());
+        };
+        self.join_node(&**first, &[]).await?;
+        let members = &nodes[..1];
+        for node in rest {
+            self.join_node(&**node, members).await?;
+        }
+        Ok(())
+    }
+
+    async fn join_node(
+        &self,
+        node: &DynLocalNode,
+        members: &[Arc<DynLocalNode>],
+    ) -> Result<(), NodeError> {
+        if let Some(seed) = members.first() {
+            let seed: &DynNode = &**seed;
+            return node.join(seed).await;
+        }
+        if self.config_provider.get_config().join_addresses.is_empty() {
+            node.create().await;
+            Ok(())
+        } else {
+            self.join_through_seeds(node).await
+        }
+    }
+
+    async fn leave_ring(&self, nodes: &[Arc<DynLocalNode>]) {
+        let deadline = self
+            .config_provider
+            .get_config()
+            .membership_config
+            .drain_deadline;
+        let leave = async {
+            for node in nodes {
+                if let Err(e) = node.leave().await {
+                    warn!("Node {} failed to leave the ring: {}", node.id(), e);
+                }
+            }
+        };
+        match timeout(deadline, leave).await {
+            Ok(()) => info!("All nodes left the ring"),
+            Err(_) => warn!("Leaving the ring took longer than {:?}", deadline),
+        }
+    }
+}
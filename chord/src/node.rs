@@ -6,30 +6,42 @@
  * https://opensource.org/licenses/MIT.
  */
 
-use std::ops::Bound;
+use std::pin::Pin;
 use std::sync::Arc;
 
-use cached::{Cached, TimedSizedCache};
-use futures::future::BoxFuture;
-use futures::FutureExt;
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt};
 use thiserror::Error;
 use tokio::sync::{Mutex, RwLock};
 use tonic::{async_trait, Code, Status};
 
-use chord_types::finger_table::FingerTable;
+use chord_types::chord_id::ChordId;
+use chord_types::finger_table::{FingerTable, FingerTableEntry, FingerTableStats};
+use chord_types::hashing::{HashAlgorithm, KeyHasher};
 use chord_types::node_info::NodeInfo;
+use chord_types::ring_interval::RingInterval;
 
+use crate::config::{ConfigProvider, Consistency, ConsistencyConfig};
 use crate::convert::ConversionError;
+use crate::failure_detector::{FailureDetector, NodeStatus};
+use crate::lookup_driver::{LookupDriver, LookupResult};
 use crate::node_client_factory::NodeClientFactory;
-use crate::util::looping_range::LoopingRange;
+use crate::replication::{next_version, required_nodes, resolve_read, select_replicas, Replica};
+use crate::routing_cache::RoutingCache;
+use crate::storage::{BoxedStorage, StorageError};
 
 pub type DynNode = dyn Node + Send + Sync;
-pub type BoxedNode = Box<DynNode>;
+pub type DynLocalNode = dyn LocalNode + Send + Sync;
+pub type BoxedLocalNode = Box<DynLocalNode>;
 
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
 pub struct FindSuccessorParameters {
-    pub id: u64,
+    pub id: ChordId,
+    /// If `true`, the node only performs a single routing step and leaves it to the caller to
+    /// follow [FindSuccessorResult::ClosestPrecedingNode]. Otherwise, the request is forwarded
+    /// recursively until the successor is found.
     pub iterate: bool,
+    /// The number of hops the lookup has taken before reaching this node.
+    pub hops: u32,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -38,116 +50,1293 @@ pub enum FindSuccessorResult {
     ClosestPrecedingNode(NodeInfo),
 }
 
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FindSuccessorOutcome {
+    pub result: FindSuccessorResult,
+    /// The length of the path the lookup has taken so far.
+    pub hops: u32,
+}
+
+/// Selects the entries a node hands over to the new owner of their keys.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TransferRangeParameters {
+    /// The entries whose keys are hashed into `(start, end]` are transferred.
+    pub start: ChordId,
+    pub end: ChordId,
+    /// The last key the caller has received. The transfer continues with the keys after it.
+    pub resume_after: Option<Vec<u8>>,
+    /// If `true`, the node deletes the entries up to and including `resume_after`, because the
+    /// caller has stored them and the node is no longer responsible for them.
+    pub remove: bool,
+}
+
+/// Batches of entries, ordered by key.
+pub type TransferStream = Pin<Box<dyn Stream<Item = Result<Vec<Replica>, NodeError>> + Send>>;
+
+/// The parameters all nodes of a ring have to agree on.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RingParameters {
+    pub id_bits: u32,
+    pub hash_algorithm: HashAlgorithm,
+}
+
 #[async_trait]
 pub trait Node {
-    fn id(&self) -> u64;
+    fn id(&self) -> ChordId;
+
+    fn node_info(&self) -> NodeInfo;
 
     async fn find_successor(
         &self,
         parameters: FindSuccessorParameters,
-    ) -> Result<FindSuccessorResult, NodeError>;
+    ) -> Result<FindSuccessorOutcome, NodeError>;
+
+    /// Returns the closest live predecessor, which is absent if none is known yet, e.g. right
+    /// after creating a ring.
+    async fn get_predecessor(&self) -> Result<Option<NodeInfo>, NodeError>;
+
+    /// Returns the successor list, closest successor first.
+    ///
+    /// A node that is alone in its ring is its own successor.
+    async fn get_successors(&self) -> Result<Vec<NodeInfo>, NodeError>;
+
+    /// Returns the predecessor list, closest predecessor first.
+    async fn get_predecessors(&self) -> Result<Vec<NodeInfo>, NodeError>;
+
+    /// Checks whether the node is reachable, without doing any other work.
+    async fn ping(&self) -> Result<(), NodeError>;
+
+    async fn get_ring_parameters(&self) -> Result<RingParameters, NodeError>;
+
+    /// Tells the node that `node` might be its predecessor.
+    async fn notify(&self, node: NodeInfo) -> Result<(), NodeError>;
+
+    /// Tells the node that its successor `leaving` leaves the ring, handing over the successor
+    /// list of the leaving node.
+    async fn successor_leaving(
+        &self,
+        leaving: NodeInfo,
+        successors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError>;
+
+    /// Tells the node that its predecessor `leaving` leaves the ring, handing over the predecessor
+    /// list of the leaving node.
+    async fn predecessor_leaving(
+        &self,
+        leaving: NodeInfo,
+        predecessors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError>;
+
+    /// Returns the value stored under the application key `key`.
+    ///
+    /// Above [Consistency::One], the node also reads from its replicas and returns the newest of
+    /// their copies. `None` uses the default read level of the node.
+    async fn get(
+        &self,
+        key: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<Option<Vec<u8>>, NodeError>;
+
+    /// Stores `value` under the application key `key`, and waits until as many replicas as
+    /// `consistency` requires acknowledged it. `None` uses the default write level of the node.
+    ///
+    /// Fails with [NodeError::NotOwner] if the key is not hashed into the arc the node is
+    /// responsible for, and with [NodeError::QuorumNotReached] if too few replicas acknowledged
+    /// the write. The write is not rolled back in that case: the node stores the value before
+    /// forwarding it, so it and any replicas that received it keep the value, reads may return it,
+    /// and it reaches the remaining replicas with the next synchronization.
+    async fn put(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<(), NodeError>;
+
+    /// Removes the application key `key`, returning whether it was stored.
+    ///
+    /// Fails like [Node::put], using the default write level of the node.
+    async fn delete(&self, key: Vec<u8>) -> Result<bool, NodeError>;
 
-    async fn get_predecessor(&self) -> Result<NodeInfo, NodeError>;
+    /// Returns the copy of the application key `key` this node stores, along with its version,
+    /// without reading from other nodes. Deleted keys are returned with no value.
+    async fn get_replica(&self, key: Vec<u8>) -> Result<Option<Replica>, NodeError>;
+
+    /// Stores copies of entries owned by a predecessor, regardless of ownership.
+    ///
+    /// Copies older than the one stored already are ignored.
+    async fn replicate(&self, replicas: Vec<Replica>) -> Result<(), NodeError>;
+
+    /// Removes the copies of the entries whose keys are hashed into `(start, end]`, because the
+    /// node no longer replicates them. Entries the node owns itself are kept.
+    async fn drop_replicas(&self, start: ChordId, end: ChordId) -> Result<(), NodeError>;
+
+    /// Streams the stored entries in a range of the ring, e.g. to a node that took over part of
+    /// the arc of this node.
+    ///
+    /// The stream is throttled according to the [HandoffConfig](crate::config::HandoffConfig).
+    async fn transfer_range(
+        &self,
+        parameters: TransferRangeParameters,
+    ) -> Result<TransferStream, NodeError>;
+}
+
+/// A node that is hosted by this process, as opposed to a client for a remote node.
+#[async_trait]
+pub trait LocalNode: Node {
+    /// Creates a new ring consisting only of this node.
+    async fn create(&self);
+
+    /// Joins the ring `seed` is part of.
+    ///
+    /// Fails if the ring uses different [RingParameters] than this node.
+    async fn join(&self, seed: &DynNode) -> Result<(), NodeError>;
+
+    /// Leaves the ring by handing the owned keys over to the closest successor, and introducing
+    /// the closest predecessor and successor to each other, so that they do not have to detect the
+    /// departure through failed requests.
+    ///
+    /// Each step is attempted even if a previous one fails, in which case the first error is
+    /// returned.
+    async fn leave(&self) -> Result<(), NodeError>;
+
+    /// Verifies the immediate successor and tells it about this node, then refreshes the
+    /// successor and predecessor lists from the closest live neighbors.
+    ///
+    /// This is run periodically, so that newly joined and failed nodes are eventually picked up.
+    async fn stabilize(&self) -> Result<(), NodeError>;
+
+    /// Refreshes the finger at `index`, which points to the successor of `id + 2^index`.
+    async fn fix_finger(&self, index: usize) -> Result<(), NodeError>;
+
+    /// Finds the node responsible for the application key `key`.
+    async fn lookup_key(&self, key: &[u8]) -> Result<LookupResult, NodeError>;
+
+    /// Populates all fingers at once, which is used right after joining.
+    ///
+    /// Consecutive fingers that share the same node are filled without an additional lookup.
+    async fn fill_fingers(&self) -> Result<(), NodeError>;
+
+    async fn get_finger_stats(&self) -> FingerTableStats;
+
+    /// The number of keys the node owns, without deleted keys and the copies it stores for other
+    /// nodes.
+    async fn key_count(&self) -> Result<usize, NodeError>;
 }
 
 pub struct NodeImpl {
-    pub id: u64,
+    pub node_info: NodeInfo,
     finger_table: RwLock<FingerTable>,
-    node_statuses: Mutex<TimedSizedCache<NodeInfo, BoxFuture<'static, NodeStatus>>>,
     grpc_node_client_factory: Arc<dyn NodeClientFactory>,
+    lookup_driver: Arc<dyn LookupDriver>,
+    failure_detector: Arc<dyn FailureDetector>,
+    /// Shared with the other nodes of this process.
+    routing_cache: Arc<dyn RoutingCache>,
+    storage: BoxedStorage,
+    /// Held while a stored entry is compared with a new copy and replaced.
+    writes: Mutex<()>,
+    replica_sync: Arc<Mutex<ReplicaSync>>,
+    handoff: Mutex<Option<Handoff>>,
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+/// The transfer of the keys a node took over when it joined, from their previous owner.
+struct Handoff {
+    source: NodeInfo,
+    /// The predecessor of the node, which bounds the transferred range. Not known until the
+    /// predecessor notifies the node.
+    start: Option<ChordId>,
+    /// The last key that was received, if the transfer has started.
+    resume_after: Option<Vec<u8>>,
+}
+
+/// The state the replicas of a node were last brought up to date with.
+#[derive(Default)]
+struct ReplicaSync {
+    predecessor: Option<ChordId>,
+    replicas: Vec<NodeInfo>,
 }
 
 impl NodeImpl {
-    pub fn new(id: u64, client_factory: Arc<dyn NodeClientFactory>) -> Self {
+    pub fn new(
+        node_info: NodeInfo,
+        client_factory: Arc<dyn NodeClientFactory>,
+        lookup_driver: Arc<dyn LookupDriver>,
+        failure_detector: Arc<dyn FailureDetector>,
+        routing_cache: Arc<dyn RoutingCache>,
+        storage: BoxedStorage,
+        config_provider: Arc<dyn ConfigProvider>,
+    ) -> Self {
         Self {
-            id,
-            finger_table: Default::default(),
-            node_statuses: Mutex::new(TimedSizedCache::with_size_and_lifespan(1024, 60)),
+            finger_table: RwLock::new(FingerTable::new(node_info.id)),
+            node_info,
             grpc_node_client_factory: client_factory,
+            lookup_driver,
+            failure_detector,
+            routing_cache,
+            storage,
+            writes: Mutex::default(),
+            replica_sync: Arc::default(),
+            handoff: Mutex::default(),
+            config_provider,
         }
     }
 }
 
-enum NodeStatus {
-    Alive,
-    Dead,
-}
-
 impl NodeImpl {
-    fn get_node(&self, node_info: &NodeInfo) -> BoxedNode {
+    fn get_node(&self, node_info: &NodeInfo) -> Arc<DynNode> {
         self.grpc_node_client_factory.create_node_client(node_info)
     }
 
-    async fn check_node(&self, node_info: NodeInfo) -> NodeStatus {
-        self.node_statuses
-            .lock()
+    async fn check_node(&self, node_info: &NodeInfo) -> NodeStatus {
+        if *node_info == self.node_info {
+            return NodeStatus::Alive;
+        }
+        self.failure_detector.check_node(node_info).await
+    }
+
+    /// Shares the nodes of a successor or predecessor list with the other nodes of this process.
+    fn share_neighbors(&self, list: &[FingerTableEntry]) {
+        for entry in list {
+            self.routing_cache.learn(&entry.node_info);
+        }
+    }
+
+    async fn lookup_finger(&self, index: usize) -> Result<NodeInfo, NodeError> {
+        let start = self.finger_table.read().await.finger_start(index);
+        Ok(self
+            .lookup_driver
+            .find_successor(self, start, true)
+            .await?
+            .successor)
+    }
+
+    async fn get_live_successor(&self) -> Option<NodeInfo> {
+        let successors = self.finger_table.read().await.get_successors().clone();
+        for successor in successors {
+            match self.check_node(&successor.node_info).await {
+                NodeStatus::Alive => return Some(successor.node_info),
+                status => {
+                    log::debug!("successor {} is {:?}", successor.node_info.id, status);
+                }
+            };
+        }
+        None
+    }
+
+    /// Builds a successor or predecessor list from the closest live neighbor and the list reported
+    /// by that neighbor.
+    ///
+    /// The list ends where it would wrap around to this node, and holds at most r entries.
+    fn neighbor_list(
+        &self,
+        closest: NodeInfo,
+        rest: impl IntoIterator<Item = NodeInfo>,
+    ) -> Vec<FingerTableEntry> {
+        let length = self
+            .config_provider
+            .get_config()
+            .maintenance_config
+            .neighbor_list_length;
+        let mut list = vec![closest];
+        for node in rest {
+            if node == self.node_info || list.len() >= length {
+                break;
+            }
+            if !list.contains(&node) {
+                list.push(node);
+            }
+        }
+        list.into_iter().map(FingerTableEntry::new).collect()
+    }
+
+    /// Removes `leaving` from a successor or predecessor list. If it was the closest neighbor,
+    /// the neighbors it handed over take its place.
+    fn neighbor_list_without(
+        &self,
+        list: &[FingerTableEntry],
+        leaving: &NodeInfo,
+        handed_over: Vec<NodeInfo>,
+    ) -> Vec<FingerTableEntry> {
+        let remaining = list
+            .iter()
+            .map(|entry| entry.node_info.clone())
+            .filter(|node| node != leaving);
+        if list.first().map(|entry| &entry.node_info) != Some(leaving) {
+            return remaining.map(FingerTableEntry::new).collect();
+        }
+        let mut candidates = handed_over
+            .into_iter()
+            .filter(|node| node != leaving)
+            .chain(remaining);
+        match candidates.next() {
+            Some(closest) => self.neighbor_list(closest, candidates),
+            None => Vec::new(),
+        }
+    }
+
+    async fn stabilize_predecessors(&self) {
+        let Some(predecessor) = self.get_live_predecessor().await else {
+            // Forget the predecessors that are known to be dead, but keep the suspected ones.
+            let predecessors = self.finger_table.read().await.get_predecessors().clone();
+            let mut remaining = Vec::new();
+            for entry in predecessors {
+                if self.check_node(&entry.node_info).await != NodeStatus::Dead {
+                    remaining.push(entry);
+                }
+            }
+            *self.finger_table.write().await.get_predecessors_mut() = remaining;
+            return;
+        };
+        let predecessors = if predecessor == self.node_info {
+            Vec::new()
+        } else {
+            match self.get_node(&predecessor).get_predecessors().await {
+                Ok(predecessors) => predecessors,
+                Err(e) => {
+                    log::debug!("failed to get predecessors of {}: {}", predecessor.id, e);
+                    entries_after(
+                        self.finger_table.read().await.get_predecessors(),
+                        &predecessor,
+                    )
+                }
+            }
+        };
+        let predecessors = self.neighbor_list(predecessor, predecessors);
+        self.share_neighbors(&predecessors);
+        *self.finger_table.write().await.get_predecessors_mut() = predecessors;
+    }
+
+    async fn get_live_predecessor(&self) -> Option<NodeInfo> {
+        let predecessors = self.finger_table.read().await.get_predecessors().clone();
+        for predecessor in predecessors {
+            match self.check_node(&predecessor.node_info).await {
+                NodeStatus::Alive => return Some(predecessor.node_info),
+                status => {
+                    log::debug!("predecessor {} is {:?}", predecessor.node_info.id, status);
+                }
+            };
+        }
+        None
+    }
+
+    /// Fails unless `key` is hashed into the arc between the live predecessor and this node.
+    ///
+    /// Without a known predecessor, the node is responsible for every key.
+    async fn check_owner(&self, key: &[u8]) -> Result<(), NodeError> {
+        let id = self.config_provider.get_config().hash_algorithm.hash(key);
+        match self.get_live_predecessor().await {
+            Some(predecessor)
+                if !RingInterval::open_closed(predecessor.id, self.node_info.id).contains(id) =>
+            {
+                Err(NodeError::not_owner(id, self.node_info.id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The nodes that store copies of the entries this node owns.
+    ///
+    /// These are the closest live successors hosted by other processes, one per process.
+    async fn get_replicas(&self) -> Vec<NodeInfo> {
+        self.replicas_of(&self.node_info).await
+    }
+
+    /// The nodes that store copies of the entries `owner` owns, if it is this node or its
+    /// predecessor.
+    async fn replicas_of(&self, owner: &NodeInfo) -> Vec<NodeInfo> {
+        let count = self
+            .config_provider
+            .get_config()
+            .replication_config
+            .replication_factor
+            .saturating_sub(1);
+        if count == 0 {
+            return Vec::new();
+        }
+        let successors = self.finger_table.read().await.get_successors().clone();
+        let mut candidates = Vec::new();
+        if *owner != self.node_info {
+            candidates.push(self.node_info.clone());
+        }
+        for successor in successors {
+            if self.check_node(&successor.node_info).await != NodeStatus::Dead {
+                candidates.push(successor.node_info);
+            }
+        }
+        select_replicas(owner, candidates, count)
+    }
+
+    /// The number of nodes, including this one, a request with `consistency` has to reach.
+    fn required_nodes(
+        &self,
+        consistency: Option<Consistency>,
+        default: impl FnOnce(&ConsistencyConfig) -> Consistency,
+    ) -> Result<usize, NodeError> {
+        let config = self.config_provider.get_config();
+        required_nodes(
+            consistency.unwrap_or_else(|| default(&config.consistency_config)),
+            config.replication_config.replication_factor,
+        )
+    }
+
+    /// Sends `replicas` to all replicas of this node at once, and waits until `required` of them
+    /// acknowledged.
+    ///
+    /// This node has stored the entries already. If too few replicas acknowledge, the write fails
+    /// but stays partly applied, see [Node::put].
+    ///
+    /// The remaining replicas are updated in the background. Replicas that cannot be reached
+    /// receive all owned entries again with the next [NodeImpl::sync_replicas].
+    async fn forward_to_replicas(
+        &self,
+        replicas: Vec<Replica>,
+        required: usize,
+    ) -> Result<(), NodeError> {
+        let mut pending: FuturesUnordered<_> = self
+            .get_replicas()
             .await
-            .cache_get_or_set_with(node_info, || {
-                let node = self.get_node(&node_info);
-                async move {
-                    match node.get_predecessor().await {
-                        Ok(_) => NodeStatus::Alive,
-                        Err(_) => NodeStatus::Dead,
-                    };
-                    NodeStatus::Alive
+            .into_iter()
+            .map(|node| {
+                let client = self.get_node(&node);
+                let replicas = replicas.clone();
+                let replica_sync = self.replica_sync.clone();
+                tokio::spawn(async move {
+                    let result = client.replicate(replicas).await;
+                    if let Err(e) = &result {
+                        log::warn!("failed to replicate to {}: {}", node.id, e);
+                        let mut sync = replica_sync.lock().await;
+                        sync.replicas.retain(|other| *other != node);
+                    }
+                    result.is_ok()
+                })
+            })
+            .collect();
+        let mut acknowledged = 0;
+        while acknowledged < required {
+            match pending.next().await {
+                Some(Ok(true)) => acknowledged += 1,
+                Some(_) => {}
+                None => break,
+            }
+        }
+        if acknowledged < required {
+            // Counting this node, which applied the write already
+            return Err(NodeError::quorum_not_reached(
+                required + 1,
+                acknowledged + 1,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copies all owned entries to the nodes that became replicas since the last call, or to all
+    /// replicas if this node took over the keys of a failed predecessor.
+    ///
+    /// Nodes that are no longer replicas are told to drop their copies, as are replicas that do
+    /// not replicate the entries a new predecessor took over from this node.
+    async fn sync_replicas(&self) {
+        let live_predecessor = self.get_live_predecessor().await;
+        let predecessor = live_predecessor.as_ref().map(|node| node.id);
+        let replicas = self.get_replicas().await;
+        let mut sync = self.replica_sync.lock().await;
+        let grew = match (sync.predecessor, predecessor) {
+            (Some(old), Some(new)) => RingInterval::open(new, self.node_info.id).contains(old),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        // The keys in (old, new] now belong to the new predecessor, or to nodes before it, all of
+        // which are replicated by a subset of the replicas of the new predecessor
+        let handed_over = match (sync.predecessor, &live_predecessor) {
+            (Some(old), Some(new))
+                if RingInterval::open(old, self.node_info.id).contains(new.id) =>
+            {
+                Some((old, self.replicas_of(new).await))
+            }
+            _ => None,
+        };
+        let start = predecessor.unwrap_or(self.node_info.id);
+        for node in &sync.replicas {
+            let keeps_owned = replicas.contains(node);
+            let end = if keeps_owned {
+                start
+            } else {
+                self.node_info.id
+            };
+            let start = match &handed_over {
+                Some((old, new_replicas)) if !new_replicas.contains(node) => *old,
+                _ if !keeps_owned => start,
+                _ => continue,
+            };
+            if let Err(e) = self.get_node(node).drop_replicas(start, end).await {
+                log::warn!("failed to drop the replicas on {}: {}", node.id, e);
+            }
+        }
+        let mut synced = Vec::new();
+        let mut entries = None;
+        for node in replicas {
+            if !grew && sync.replicas.contains(&node) {
+                synced.push(node);
+                continue;
+            }
+            if entries.is_none() {
+                match self.owned_entries(predecessor).await {
+                    Ok(owned) => entries = Some(owned),
+                    Err(e) => {
+                        log::warn!("failed to read the entries of {}: {}", self.node_info.id, e);
+                        return;
+                    }
                 }
-                .boxed()
+            }
+            match self.send_replicas(&node, entries.as_ref().unwrap()).await {
+                Ok(()) => synced.push(node),
+                Err(e) => log::warn!("failed to replicate to {}: {}", node.id, e),
+            }
+        }
+        *sync = ReplicaSync {
+            predecessor,
+            replicas: synced,
+        };
+    }
+
+    async fn read_local(&self, key: &[u8]) -> Result<Option<Replica>, NodeError> {
+        match self.storage.get(key).await? {
+            Some(bytes) => Ok(Some(Replica::decode_stored(key.to_vec(), &bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// All stored entries, including deleted keys.
+    async fn local_entries(&self) -> Result<Vec<Replica>, NodeError> {
+        self.storage
+            .entries()
+            .await?
+            .into_iter()
+            .map(|(key, bytes)| Ok(Replica::decode_stored(key, &bytes)?))
+            .collect()
+    }
+
+    /// Stores `replica` unless the stored copy is at least as new.
+    async fn write_newer(&self, replica: Replica) -> Result<(), NodeError> {
+        let _writes = self.writes.lock().await;
+        if let Some(stored) = self.read_local(&replica.key).await? {
+            if stored.version >= replica.version {
+                return Ok(());
+            }
+        }
+        let bytes = replica.encode_stored();
+        self.storage.put(replica.key, bytes).await?;
+        Ok(())
+    }
+
+    /// Stores a new version of `key`, which replaces the stored copy, and returns it along with
+    /// the copy it replaced.
+    async fn write_next(
+        &self,
+        key: Vec<u8>,
+        value: Option<Vec<u8>>,
+    ) -> Result<(Replica, Option<Replica>), NodeError> {
+        let _writes = self.writes.lock().await;
+        let stored = self.read_local(&key).await?;
+        let replica = Replica {
+            key,
+            value,
+            version: next_version(stored.as_ref().map(|stored| stored.version)),
+        };
+        self.storage
+            .put(replica.key.clone(), replica.encode_stored())
+            .await?;
+        Ok((replica, stored))
+    }
+
+    /// The stored entries whose keys are hashed into the arc after `predecessor`.
+    async fn owned_entries(
+        &self,
+        predecessor: Option<ChordId>,
+    ) -> Result<Vec<Replica>, NodeError> {
+        let algorithm = self.config_provider.get_config().hash_algorithm;
+        Ok(self
+            .local_entries()
+            .await?
+            .into_iter()
+            .filter(|replica| match predecessor {
+                Some(predecessor) => RingInterval::open_closed(predecessor, self.node_info.id)
+                    .contains(algorithm.hash(&replica.key)),
+                None => true,
             })
-            .await
+            .collect())
+    }
+
+    async fn send_replicas(&self, node: &NodeInfo, replicas: &[Replica]) -> Result<(), NodeError> {
+        let batch_size = self
+            .config_provider
+            .get_config()
+            .replication_config
+            .batch_size
+            .max(1);
+        let client = self.get_node(node);
+        for batch in replicas.chunks(batch_size) {
+            client.replicate(batch.to_vec()).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends the stored entries whose keys are hashed into `(start, self]` to `successor`, which
+    /// takes them over when this node leaves.
+    async fn hand_over(&self, successor: &NodeInfo, start: ChordId) -> Result<(), NodeError> {
+        let mut batches = self
+            .transfer_range(TransferRangeParameters {
+                start,
+                end: self.node_info.id,
+                resume_after: None,
+                remove: false,
+            })
+            .await?;
+        let client = self.get_node(successor);
+        while let Some(batch) = batches.next().await {
+            // Copies the successor accepted meanwhile are kept if they are newer
+            client.replicate(batch?).await?;
+        }
+        Ok(())
+    }
+
+    /// Continues the transfer of the keys this node took over when it joined, once its
+    /// predecessor, and thereby the range, is known.
+    ///
+    /// An interrupted transfer is resumed after the last received key on the next call.
+    async fn receive_handoff(&self) -> Result<(), NodeError> {
+        let (source, start, mut resume_after) = match &*self.handoff.lock().await {
+            Some(Handoff {
+                source,
+                start: Some(start),
+                resume_after,
+            }) => (source.clone(), *start, resume_after.clone()),
+            _ => return Ok(()),
+        };
+        // The source keeps the entries if it replicates them for this node
+        let remove = !self.get_replicas().await.contains(&source);
+        let client = self.get_node(&source);
+        loop {
+            let mut batches = client
+                .transfer_range(TransferRangeParameters {
+                    start,
+                    end: self.node_info.id,
+                    resume_after: resume_after.clone(),
+                    remove,
+                })
+                .await?;
+            let mut received = false;
+            while let Some(batch) = batches.next().await {
+                for replica in batch? {
+                    resume_after = Some(replica.key.clone());
+                    // Writes this node accepted meanwhile are newer
+                    self.write_newer(replica).await?;
+                    received = true;
+                }
+                if let Some(handoff) = self.handoff.lock().await.as_mut() {
+                    handoff.resume_after = resume_after.clone();
+                }
+            }
+            // An empty response acknowledges the last batch, so the source can remove it
+            if !received {
+                break;
+            }
+        }
+        log::info!(
+            "node {} took over the keys in ({}, {}] from {}",
+            self.node_info.id,
+            start,
+            self.node_info.id,
+            source.id
+        );
+        *self.handoff.lock().await = None;
+        // The replicas have to receive the keys that were handed over as well
+        self.replica_sync.lock().await.replicas.clear();
+        Ok(())
+    }
+
+    /// The candidates for the next hop towards `id`, closest first.
+    ///
+    /// Besides the nodes in the finger table, these are the nodes the other nodes of this process
+    /// have learned about.
+    fn preceding_nodes(&self, finger_table: &FingerTable, id: ChordId) -> Vec<NodeInfo> {
+        let mut nodes = finger_table.preceding_nodes(id);
+        let limit = self
+            .config_provider
+            .get_config()
+            .maintenance_config
+            .neighbor_list_length;
+        for node in self
+            .routing_cache
+            .preceding_nodes(self.node_info.id, id, limit)
+        {
+            if !nodes.iter().any(|known| known.id == node.id) {
+                nodes.push(node);
+            }
+        }
+        nodes.sort_by_key(|node| node.id.distance_to(id));
+        nodes
+    }
+
+    /// Performs a single routing step for `id` using only the knowledge of this process.
+    async fn find_successor_step(&self, id: ChordId) -> Result<FindSuccessorResult, NodeError> {
+        if id == self.node_info.id {
+            return Ok(FindSuccessorResult::Successor(self.node_info.clone()));
+        }
+        // Checking the candidates may take a while, so they are copied out of the finger table
+        let (successors, preceding_nodes) = {
+            let finger_table = self.finger_table.read().await;
+            (
+                finger_table.get_successors().clone(),
+                self.preceding_nodes(&finger_table, id),
+            )
+        };
+        // find direct successor
+        for successor in successors {
+            if RingInterval::open_closed(self.node_info.id, successor.node_info.id).contains(id) {
+                match self.check_node(&successor.node_info).await {
+                    NodeStatus::Alive => {
+                        return Ok(FindSuccessorResult::Successor(successor.node_info))
+                    }
+                    status => {
+                        log::debug!("successor {} is {:?}", successor.node_info.id, status);
+                    }
+                }
+            }
+        }
+        // find the closest preceding node, falling back to farther ones if it is not alive
+        for node in preceding_nodes {
+            match self.check_node(&node).await {
+                NodeStatus::Alive => return Ok(FindSuccessorResult::ClosestPrecedingNode(node)),
+                status => {
+                    if status == NodeStatus::Dead {
+                        self.routing_cache.forget(node.id);
+                    }
+                    log::debug!("node {} is {:?}", node.id, status);
+                }
+            }
+        }
+        Err(NodeError::no_route(id))
     }
 }
 
 #[async_trait]
 impl Node for NodeImpl {
-    fn id(&self) -> u64 {
-        self.id
+    fn id(&self) -> ChordId {
+        self.node_info.id
+    }
+
+    fn node_info(&self) -> NodeInfo {
+        self.node_info.clone()
     }
 
     async fn find_successor(
         &self,
-        FindSuccessorParameters { id, .. }: FindSuccessorParameters,
-    ) -> Result<FindSuccessorResult, NodeError> {
-        let finger_table = self.finger_table.read().await;
-        // find direct successor
-        for successor in finger_table.get_successors() {
-            if (
-                Bound::Excluded(self.id),
-                Bound::Included(successor.node_info.id),
+        FindSuccessorParameters { id, iterate, hops }: FindSuccessorParameters,
+    ) -> Result<FindSuccessorOutcome, NodeError> {
+        let result = self.find_successor_step(id).await?;
+        match result {
+            FindSuccessorResult::ClosestPrecedingNode(next) if !iterate => {
+                let max_hops = self.config_provider.get_config().lookup_config.max_hops;
+                if hops >= max_hops {
+                    return Err(NodeError::max_hops_exceeded(id, max_hops));
+                }
+                self.get_node(&next)
+                    .find_successor(FindSuccessorParameters {
+                        id,
+                        iterate,
+                        hops: hops + 1,
+                    })
+                    .await
+            }
+            result => Ok(FindSuccessorOutcome { result, hops }),
+        }
+    }
+
+    async fn get_predecessor(&self) -> Result<Option<NodeInfo>, NodeError> {
+        Ok(self.get_live_predecessor().await)
+    }
+
+    async fn get_successors(&self) -> Result<Vec<NodeInfo>, NodeError> {
+        Ok(self
+            .finger_table
+            .read()
+            .await
+            .get_successors()
+            .iter()
+            .map(|entry| entry.node_info.clone())
+            .collect())
+    }
+
+    async fn get_predecessors(&self) -> Result<Vec<NodeInfo>, NodeError> {
+        Ok(self
+            .finger_table
+            .read()
+            .await
+            .get_predecessors()
+            .iter()
+            .map(|entry| entry.node_info.clone())
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<(), NodeError> {
+        Ok(())
+    }
+
+    async fn get_ring_parameters(&self) -> Result<RingParameters, NodeError> {
+        Ok(RingParameters {
+            id_bits: ChordId::BITS as u32,
+            hash_algorithm: self.config_provider.get_config().hash_algorithm,
+        })
+    }
+
+    async fn notify(&self, node: NodeInfo) -> Result<(), NodeError> {
+        if node == self.node_info {
+            return Ok(());
+        }
+        let is_closer = match self.get_live_predecessor().await {
+            Some(predecessor) => {
+                RingInterval::open(predecessor.id, self.node_info.id).contains(node.id)
+            }
+            None => true,
+        };
+        if is_closer {
+            if let Some(handoff) = self.handoff.lock().await.as_mut() {
+                // Until the transfer has started, the closest predecessor bounds its range
+                if handoff.resume_after.is_none() {
+                    handoff.start = Some(node.id);
+                }
+            }
+            self.routing_cache.learn(&node);
+            let mut finger_table = self.finger_table.write().await;
+            let predecessors = entries_after(finger_table.get_predecessors(), &node);
+            *finger_table.get_predecessors_mut() = self.neighbor_list(node.clone(), predecessors);
+            log::info!("node {} has new predecessor {}", self.node_info.id, node.id);
+            finger_table.update_with(node);
+        }
+        Ok(())
+    }
+
+    async fn successor_leaving(
+        &self,
+        leaving: NodeInfo,
+        successors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError> {
+        self.failure_detector.report_left(&leaving);
+        self.routing_cache.forget(leaving.id);
+        let mut finger_table = self.finger_table.write().await;
+        let mut successors =
+            self.neighbor_list_without(finger_table.get_successors(), &leaving, successors);
+        if successors.is_empty() {
+            successors.push(FingerTableEntry::new(self.node_info.clone()));
+        }
+        finger_table.remove_node(leaving.id);
+        *finger_table.get_successors_mut() = successors;
+        log::info!(
+            "successor {} of node {} left the ring",
+            leaving.id,
+            self.node_info.id
+        );
+        Ok(())
+    }
+
+    async fn predecessor_leaving(
+        &self,
+        leaving: NodeInfo,
+        predecessors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError> {
+        self.failure_detector.report_left(&leaving);
+        self.routing_cache.forget(leaving.id);
+        let mut finger_table = self.finger_table.write().await;
+        let mut predecessors =
+            self.neighbor_list_without(finger_table.get_predecessors(), &leaving, predecessors);
+        // a node is only its own predecessor after creating a ring
+        predecessors.retain(|entry| entry.node_info != self.node_info);
+        finger_table.remove_node(leaving.id);
+        *finger_table.get_predecessors_mut() = predecessors;
+        log::info!(
+            "predecessor {} of node {} left the ring",
+            leaving.id,
+            self.node_info.id
+        );
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        key: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<Option<Vec<u8>>, NodeError> {
+        let required = self.required_nodes(consistency, |config| config.read)?;
+        let copy = self.read_local(&key).await?;
+        if required == 1 {
+            return Ok(copy.and_then(|copy| copy.value));
+        }
+        let mut pending: FuturesUnordered<_> = self
+            .get_replicas()
+            .await
+            .into_iter()
+            .map(|node| {
+                let key = key.clone();
+                async move {
+                    let result = self.get_node(&node).get_replica(key).await;
+                    (node, result)
+                }
+            })
+            .collect();
+        let mut copies = vec![copy];
+        while copies.len() < required {
+            match pending.next().await {
+                Some((_, Ok(copy))) => copies.push(copy),
+                Some((node, Err(e))) => log::debug!("failed to read from {}: {}", node.id, e),
+                None => return Err(NodeError::quorum_not_reached(required, copies.len())),
+            }
+        }
+        Ok(resolve_read(copies))
+    }
+
+    async fn put(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<(), NodeError> {
+        let required = self.required_nodes(consistency, |config| config.write)?;
+        self.check_owner(&key).await?;
+        let (replica, _) = self.write_next(key, Some(value)).await?;
+        self.forward_to_replicas(vec![replica], required - 1).await
+    }
+
+    async fn delete(&self, key: Vec<u8>) -> Result<bool, NodeError> {
+        let required = self.required_nodes(None, |config| config.write)?;
+        self.check_owner(&key).await?;
+        // The key is kept as deleted, so that the deletion replaces older copies on other nodes
+        let (replica, stored) = self.write_next(key, None).await?;
+        self.forward_to_replicas(vec![replica], required - 1)
+            .await?;
+        Ok(stored.is_some_and(|stored| stored.value.is_some()))
+    }
+
+    async fn get_replica(&self, key: Vec<u8>) -> Result<Option<Replica>, NodeError> {
+        self.read_local(&key).await
+    }
+
+    async fn replicate(&self, replicas: Vec<Replica>) -> Result<(), NodeError> {
+        for replica in replicas {
+            self.write_newer(replica).await?;
+        }
+        Ok(())
+    }
+
+    async fn drop_replicas(&self, start: ChordId, end: ChordId) -> Result<(), NodeError> {
+        let algorithm = self.config_provider.get_config().hash_algorithm;
+        let range = RingInterval::open_closed(start, end);
+        let owned = match self.get_live_predecessor().await {
+            Some(predecessor) => RingInterval::open_closed(predecessor.id, self.node_info.id),
+            None => RingInterval::Full,
+        };
+        let mut dropped = 0;
+        for (key, _) in self.storage.entries().await? {
+            let id = algorithm.hash(&key);
+            if range.contains(id) && !owned.contains(id) {
+                self.storage.delete(&key).await?;
+                dropped += 1;
+            }
+        }
+        log::debug!(
+            "node {} dropped {} replicas in ({}, {}]",
+            self.node_info.id,
+            dropped,
+            start,
+            end
+        );
+        Ok(())
+    }
+
+    async fn transfer_range(
+        &self,
+        TransferRangeParameters {
+            start,
+            end,
+            resume_after,
+            remove,
+        }: TransferRangeParameters,
+    ) -> Result<TransferStream, NodeError> {
+        let config = self.config_provider.get_config();
+        let range = RingInterval::open_closed(start, end);
+        let mut entries: Vec<_> = self
+            .local_entries()
+            .await?
+            .into_iter()
+            .filter(|replica| range.contains(config.hash_algorithm.hash(&replica.key)))
+            .collect();
+        entries.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+        let sent = match &resume_after {
+            Some(last) => entries.partition_point(|replica| replica.key <= *last),
+            None => 0,
+        };
+        if remove {
+            for replica in &entries[..sent] {
+                self.storage.delete(&replica.key).await?;
+            }
+        }
+        let batch_size = config.handoff_config.batch_size.max(1);
+        let batch_delay = config.handoff_config.batch_delay;
+        let batches: Vec<Vec<Replica>> = entries[sent..]
+            .chunks(batch_size)
+            .map(<[Replica]>::to_vec)
+            .collect();
+        Ok(Box::pin(stream::iter(batches.into_iter().enumerate()).then(
+            move |(index, batch)| async move {
+                if index > 0 {
+                    tokio::time::sleep(batch_delay).await;
+                }
+                Ok(batch)
+            },
+        )))
+    }
+}
+
+#[async_trait]
+impl LocalNode for NodeImpl {
+    async fn create(&self) {
+        let mut finger_table = self.finger_table.write().await;
+        finger_table.get_predecessors_mut().clear();
+        *finger_table.get_successors_mut() = vec![FingerTableEntry::new(self.node_info.clone())];
+        self.routing_cache.learn(&self.node_info);
+        log::info!("node {} created a new ring", self.node_info.id);
+    }
+
+    async fn join(&self, seed: &DynNode) -> Result<(), NodeError> {
+        let local = self.get_ring_parameters().await?;
+        let remote = seed.get_ring_parameters().await?;
+        if local != remote {
+            return Err(NodeError::incompatible_ring(local, remote));
+        }
+        let LookupResult { successor, hops } = self
+            .lookup_driver
+            .find_successor(seed, self.node_info.id, true)
+            .await?;
+        // The successor owned the keys of this node so far
+        *self.handoff.lock().await = (successor != self.node_info).then(|| Handoff {
+            source: successor.clone(),
+            start: None,
+            resume_after: None,
+        });
+        let mut finger_table = self.finger_table.write().await;
+        finger_table.get_predecessors_mut().clear();
+        log::info!(
+            "node {} joined the ring via {} with successor {} after {} hops",
+            self.node_info.id,
+            seed.id(),
+            successor.id,
+            hops,
+        );
+        self.routing_cache.learn(&self.node_info);
+        self.routing_cache.learn(&successor);
+        *finger_table.get_successors_mut() = vec![FingerTableEntry::new(successor)];
+        Ok(())
+    }
+
+    async fn leave(&self) -> Result<(), NodeError> {
+        self.routing_cache.forget(self.node_info.id);
+        let successor = self.get_live_successor().await;
+        let predecessor = self.get_live_predecessor().await;
+        let (successors, predecessors) = {
+            let finger_table = self.finger_table.read().await;
+            (
+                entries_after(finger_table.get_successors(), &self.node_info),
+                entries_after(finger_table.get_predecessors(), &self.node_info),
             )
-                .contains_looping(&id)
+        };
+        // Both neighbors are told even if one of them fails, and the first error is returned
+        let mut result = Ok(());
+        if let Some(successor) = successor.filter(|node| *node != self.node_info) {
+            let start = predecessor.as_ref().map_or(self.node_info.id, |node| node.id);
+            if let Err(e) = self.hand_over(&successor, start).await {
+                log::warn!(
+                    "failed to hand the keys of {} over to {}: {}",
+                    self.node_info.id,
+                    successor.id,
+                    e
+                );
+                result = result.and(Err(e));
+            }
+            if let Err(e) = self
+                .get_node(&successor)
+                .predecessor_leaving(self.node_info.clone(), predecessors)
+                .await
             {
-                match self.check_node(successor.node_info).await {
-                    NodeStatus::Alive => {
-                        return Ok(FindSuccessorResult::Successor(successor.node_info))
-                    }
-                    NodeStatus::Dead => {
-                        log::debug!("successor {} is dead", successor.node_info.id);
-                    }
+                log::warn!(
+                    "failed to notify successor {} of leaving: {}",
+                    successor.id,
+                    e
+                );
+                result = result.and(Err(e));
+            }
+        }
+        if let Some(predecessor) = predecessor.filter(|node| *node != self.node_info) {
+            if let Err(e) = self
+                .get_node(&predecessor)
+                .successor_leaving(self.node_info.clone(), successors)
+                .await
+            {
+                log::warn!(
+                    "failed to notify predecessor {} of leaving: {}",
+                    predecessor.id,
+                    e
+                );
+                result = result.and(Err(e));
+            }
+        }
+        log::info!("node {} left the ring", self.node_info.id);
+        result
+    }
+
+    async fn stabilize(&self) -> Result<(), NodeError> {
+        let Some(mut successor) = self.get_live_successor().await else {
+            return Err(NodeError::no_route(self.node_info.id));
+        };
+        let successor_predecessor = if successor == self.node_info {
+            self.get_live_predecessor().await
+        } else {
+            match self.get_node(&successor).get_predecessor().await {
+                Ok(predecessor) => predecessor,
+                Err(e) => {
+                    log::debug!("failed to get predecessor of {}: {}", successor.id, e);
+                    None
                 }
             }
+        };
+        if let Some(candidate) = successor_predecessor {
+            if RingInterval::open(self.node_info.id, successor.id).contains(candidate.id) {
+                log::info!(
+                    "node {} has new successor {}",
+                    self.node_info.id,
+                    candidate.id
+                );
+                successor = candidate;
+            }
         }
-        // find the closest preceding node
-        todo!();
+        let successors = if successor == self.node_info {
+            Vec::new()
+        } else {
+            match self.get_node(&successor).get_successors().await {
+                Ok(successors) => successors,
+                Err(e) => {
+                    log::debug!("failed to get successors of {}: {}", successor.id, e);
+                    entries_after(self.finger_table.read().await.get_successors(), &successor)
+                }
+            }
+        };
+        let successors = self.neighbor_list(successor.clone(), successors);
+        self.share_neighbors(&successors);
+        *self.finger_table.write().await.get_successors_mut() = successors;
+        if successor != self.node_info {
+            self.get_node(&successor)
+                .notify(self.node_info.clone())
+                .await?;
+        }
+        self.stabilize_predecessors().await;
+        if let Err(e) = self.receive_handoff().await {
+            log::warn!("handoff to node {} was interrupted: {}", self.node_info.id, e);
+        }
+        self.sync_replicas().await;
+        Ok(())
     }
 
-    async fn get_predecessor(&self) -> Result<NodeInfo, NodeError> {
-        let finger_table = self.finger_table.read().await;
-        for predecessor in finger_table.get_predecessors() {
-            match self.check_node(predecessor.node_info).await {
-                NodeStatus::Alive => return Ok(predecessor.node_info),
-                NodeStatus::Dead => {
-                    log::debug!("predecessor {} is dead", predecessor.node_info.id);
+    async fn lookup_key(&self, key: &[u8]) -> Result<LookupResult, NodeError> {
+        self.lookup_driver.lookup_key(self, key, true).await
+    }
+
+    async fn fix_finger(&self, index: usize) -> Result<(), NodeError> {
+        let node = self.lookup_finger(index).await?;
+        self.routing_cache.learn(&node);
+        self.finger_table.write().await.get_entries_mut()[index] =
+            Some(FingerTableEntry::new(node));
+        Ok(())
+    }
+
+    async fn fill_fingers(&self) -> Result<(), NodeError> {
+        let size = self.finger_table.read().await.get_entries().len();
+        let mut previous: Option<NodeInfo> = None;
+        for index in 0..size {
+            let start = self.finger_table.read().await.finger_start(index);
+            let node = match previous.take() {
+                Some(previous)
+                    if RingInterval::open_closed(self.node_info.id, previous.id)
+                        .contains(start) =>
+                {
+                    previous
+                }
+                _ => {
+                    let node = self.lookup_finger(index).await?;
+                    self.routing_cache.learn(&node);
+                    node
                 }
             };
+            self.finger_table.write().await.get_entries_mut()[index] =
+                Some(FingerTableEntry::new(node.clone()));
+            previous = Some(node);
         }
-        todo!()
+        Ok(())
+    }
+
+    async fn get_finger_stats(&self) -> FingerTableStats {
+        self.finger_table.read().await.get_stats()
     }
+
+    async fn key_count(&self) -> Result<usize, NodeError> {
+        let predecessor = self.get_live_predecessor().await.map(|node| node.id);
+        Ok(self
+            .owned_entries(predecessor)
+            .await?
+            .iter()
+            .filter(|replica| replica.value.is_some())
+            .count())
+    }
+}
+
+/// Returns the nodes in `entries` that come after `node`, or all of them if `node` is not part of
+/// `entries`.
+fn entries_after(entries: &[FingerTableEntry], node: &NodeInfo) -> Vec<NodeInfo> {
+    let start = entries
+        .iter()
+        .position(|entry| entry.node_info == *node)
+        .map_or(0, |position| position + 1);
+    entries[start..]
+        .iter()
+        .map(|entry| entry.node_info.clone())
+        .collect()
 }
 
 #[derive(Clone, Debug, Error)]
 pub enum NodeError {
     #[error("invalid response from node")]
-    InvalidResponse(u64),
+    InvalidResponse(ChordId),
+    #[error("no live node known to route id {0}")]
+    NoRoute(ChordId),
+    #[error("none of the endpoints of node {0} is reachable")]
+    Unreachable(ChordId),
+    #[error("lookup for id {id} exceeded the maximum of {max_hops} hops")]
+    MaxHopsExceeded { id: ChordId, max_hops: u32 },
+    #[error("the ring uses {remote:?}, but this node uses {local:?}")]
+    IncompatibleRing {
+        local: RingParameters,
+        remote: RingParameters,
+    },
+    #[error("node {node} is not responsible for id {id}")]
+    NotOwner { id: ChordId, node: ChordId },
+    #[error("invalid consistency {consistency:?} for replication factor {replication_factor}")]
+    InvalidConsistency {
+        consistency: Consistency,
+        replication_factor: usize,
+    },
+    #[error("only {reached} of the {required} nodes required for the consistency level responded")]
+    QuorumNotReached { required: usize, reached: usize },
+    #[error("the id of virtual node {0} cannot be derived without a specific advertised address")]
+    NoAdvertisedAddress(u32),
+    #[error(transparent)]
+    StorageError(#[from] StorageError),
     #[error(transparent)]
     StatusError(#[from] Status),
     #[error(transparent)]
@@ -157,10 +1346,45 @@ pub enum NodeError {
 }
 
 impl NodeError {
-    pub fn invalid_response(id: u64) -> Self {
+    pub fn invalid_response(id: ChordId) -> Self {
         NodeError::InvalidResponse(id)
     }
 
+    pub fn no_route(id: ChordId) -> Self {
+        NodeError::NoRoute(id)
+    }
+
+    pub fn unreachable(id: ChordId) -> Self {
+        NodeError::Unreachable(id)
+    }
+
+    pub fn max_hops_exceeded(id: ChordId, max_hops: u32) -> Self {
+        NodeError::MaxHopsExceeded { id, max_hops }
+    }
+
+    pub fn incompatible_ring(local: RingParameters, remote: RingParameters) -> Self {
+        NodeError::IncompatibleRing { local, remote }
+    }
+
+    pub fn not_owner(id: ChordId, node: ChordId) -> Self {
+        NodeError::NotOwner { id, node }
+    }
+
+    pub fn invalid_consistency(consistency: Consistency, replication_factor: usize) -> Self {
+        NodeError::InvalidConsistency {
+            consistency,
+            replication_factor,
+        }
+    }
+
+    pub fn quorum_not_reached(required: usize, reached: usize) -> Self {
+        NodeError::QuorumNotReached { required, reached }
+    }
+
+    pub fn no_advertised_address(index: u32) -> Self {
+        NodeError::NoAdvertisedAddress(index)
+    }
+
     pub fn status_error(status: impl Into<Status>) -> Self {
         NodeError::StatusError(status.into())
     }
@@ -172,9 +1396,619 @@ impl NodeError {
     pub fn get_code(&self) -> Code {
         match self {
             NodeError::InvalidResponse(_) => Code::InvalidArgument,
+            NodeError::NoRoute(_) => Code::Unavailable,
+            NodeError::Unreachable(_) => Code::Unavailable,
+            NodeError::MaxHopsExceeded { .. } => Code::ResourceExhausted,
+            NodeError::IncompatibleRing { .. } => Code::FailedPrecondition,
+            NodeError::NotOwner { .. } => Code::FailedPrecondition,
+            NodeError::InvalidConsistency { .. } => Code::InvalidArgument,
+            NodeError::QuorumNotReached { .. } => Code::Unavailable,
+            NodeError::NoAdvertisedAddress(_) => Code::FailedPrecondition,
+            NodeError::StorageError(storage_error) => storage_error.get_code(),
             NodeError::StatusError(_) => Code::Internal,
             NodeError::ConversionError(_) => Code::Internal,
             NodeError::Unknown => Code::Unknown,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use shaku::HasComponent;
+
+    use crate::config::{
+        Config, HandoffConfig, LookupConfig, MaintenanceConfig, RoutingCacheConfig,
+    };
+    use crate::testing::TestNetwork;
+
+    use super::*;
+
+    const NODE_COUNT: u128 = 10;
+
+    fn test_config(neighbor_list_length: usize) -> Config {
+        Config {
+            maintenance_config: MaintenanceConfig {
+                neighbor_list_length,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn ids(values: &[u128]) -> Vec<ChordId> {
+        values.iter().map(|&value| ChordId::from(value)).collect()
+    }
+
+    async fn create_ring(network: &Arc<TestNetwork>, config: Config) -> Vec<Arc<DynLocalNode>> {
+        let ids: Vec<_> = (0..NODE_COUNT)
+            .map(|i| ChordId::from(i * (u128::MAX / NODE_COUNT)))
+            .collect();
+        let nodes = network.create_nodes(&ids, config);
+        nodes[0].create().await;
+        for (i, node) in nodes.iter().enumerate().skip(1) {
+            node.join(&*nodes[0]).await.unwrap();
+            stabilize(network, &nodes[..=i], 2).await;
+        }
+        stabilize(network, &nodes, 2).await;
+        for node in &nodes {
+            node.fill_fingers().await.unwrap();
+        }
+        nodes
+    }
+
+    async fn stabilize(network: &TestNetwork, nodes: &[Arc<DynLocalNode>], rounds: usize) {
+        for _ in 0..rounds {
+            for node in nodes.iter().filter(|node| network.is_alive(node.id())) {
+                let _ = node.stabilize().await;
+            }
+        }
+    }
+
+    fn expected_successor(
+        network: &TestNetwork,
+        nodes: &[Arc<DynLocalNode>],
+        id: ChordId,
+    ) -> ChordId {
+        let mut ids: Vec<_> = nodes
+            .iter()
+            .map(|node| node.id())
+            .filter(|&id| network.is_alive(id))
+            .collect();
+        ids.sort();
+        ids.iter()
+            .copied()
+            .find(|&node| node >= id)
+            .unwrap_or(ids[0])
+    }
+
+    async fn assert_lookups_succeed(network: &TestNetwork, nodes: &[Arc<DynLocalNode>]) {
+        let keys =
+            (0..4 * NODE_COUNT).map(|i| ChordId::from(i * (u128::MAX / (4 * NODE_COUNT)) + 7));
+        for node in nodes.iter().filter(|node| network.is_alive(node.id())) {
+            for key in keys.clone() {
+                let outcome = node
+                    .find_successor(FindSuccessorParameters {
+                        id: key,
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    outcome.result,
+                    FindSuccessorResult::Successor(
+                        nodes
+                            .iter()
+                            .find(|n| n.id() == expected_successor(network, nodes, key))
+                            .unwrap()
+                            .node_info()
+                    ),
+                    "lookup of {} from {}",
+                    key,
+                    node.id()
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_successor_lists_are_filled() {
+        let network = TestNetwork::new();
+        let nodes = create_ring(&network, test_config(3)).await;
+        for (i, node) in nodes.iter().enumerate() {
+            let expected: Vec<_> = (1..=3)
+                .map(|offset| nodes[(i + offset) % nodes.len()].node_info())
+                .collect();
+            assert_eq!(node.get_successors().await.unwrap(), expected);
+            let expected: Vec<_> = (1..=3)
+                .map(|offset| nodes[(i + nodes.len() - offset) % nodes.len()].node_info())
+                .collect();
+            assert_eq!(node.get_predecessors().await.unwrap(), expected);
+        }
+        assert_lookups_succeed(&network, &nodes).await;
+    }
+
+    #[tokio::test]
+    async fn test_lookups_survive_consecutive_failures() {
+        let network = TestNetwork::new();
+        let nodes = create_ring(&network, test_config(3)).await;
+        network.kill(nodes[3].id());
+        network.kill(nodes[4].id());
+        assert_lookups_succeed(&network, &nodes).await;
+        // stale entries travel back one node per round, so r rounds clear them from every list
+        stabilize(&network, &nodes, 3).await;
+        assert_lookups_succeed(&network, &nodes).await;
+        for (i, node) in nodes.iter().enumerate() {
+            if i != 3 && i != 4 {
+                assert!(!node
+                    .get_successors()
+                    .await
+                    .unwrap()
+                    .contains(&nodes[3].node_info()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_leave_hands_over_neighbors() {
+        let network = TestNetwork::new();
+        let nodes = create_ring(&network, test_config(3)).await;
+        nodes[3].leave().await.unwrap();
+        network.kill(nodes[3].id());
+        // the neighbors know about each other without having to stabilize first
+        assert_eq!(
+            nodes[2].get_successors().await.unwrap(),
+            vec![
+                nodes[4].node_info(),
+                nodes[5].node_info(),
+                nodes[6].node_info()
+            ]
+        );
+        assert_eq!(
+            nodes[4].get_predecessors().await.unwrap(),
+            vec![
+                nodes[2].node_info(),
+                nodes[1].node_info(),
+                nodes[0].node_info()
+            ]
+        );
+        assert_lookups_succeed(&network, &nodes).await;
+    }
+
+    #[tokio::test]
+    async fn test_leave_hands_over_keys() {
+        let network = TestNetwork::new();
+        let mut nodes = create_ring(&network, handoff_config()).await;
+        let keys: Vec<_> = (0..50).map(|i| format!("key-{}", i).into_bytes()).collect();
+        for key in &keys {
+            let owner = expected_successor(&network, &nodes, HashAlgorithm::default().hash(key));
+            let owner = nodes.iter().find(|node| node.id() == owner).unwrap();
+            owner.put(key.clone(), key.clone(), None).await.unwrap();
+        }
+
+        let left = nodes.remove(3);
+        left.leave().await.unwrap();
+        network.kill(left.id());
+        stabilize(&network, &nodes, 2).await;
+        for key in &keys {
+            let owner = expected_successor(&network, &nodes, HashAlgorithm::default().hash(key));
+            let owner = nodes.iter().find(|node| node.id() == owner).unwrap();
+            assert_eq!(
+                owner.get(key.clone(), None).await.unwrap().as_ref(),
+                Some(key),
+                "key {:?}",
+                String::from_utf8_lossy(key)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_leave_two_node_ring() {
+        let network = TestNetwork::new();
+        let nodes = network.create_nodes(&ids(&[0, u128::MAX / 2]), test_config(3));
+        nodes[0].create().await;
+        nodes[1].join(&*nodes[0]).await.unwrap();
+        stabilize(&network, &nodes, 2).await;
+        nodes[1].leave().await.unwrap();
+        network.kill(nodes[1].id());
+        assert_eq!(nodes[0].get_predecessors().await.unwrap(), vec![]);
+        assert_lookups_succeed(&network, &nodes).await;
+    }
+
+    /// Checks that both a recursive lookup and an iterative one, driven by this function, arrive
+    /// at `expected`.
+    async fn assert_successor(nodes: &[Arc<DynLocalNode>], start: usize, id: u128, expected: u128) {
+        let (id, expected) = (ChordId::from(id), ChordId::from(expected));
+        let recursive = nodes[start]
+            .find_successor(FindSuccessorParameters {
+                id,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        match recursive.result {
+            FindSuccessorResult::Successor(successor) => {
+                assert_eq!(successor.id, expected, "lookup of {} from {}", id, start)
+            }
+            result => panic!("recursive lookup of {} returned {:?}", id, result),
+        }
+        let mut node = nodes[start].clone();
+        for _ in 0..nodes.len() {
+            let step = node
+                .find_successor(FindSuccessorParameters {
+                    id,
+                    iterate: true,
+                    hops: 0,
+                })
+                .await
+                .unwrap();
+            match step.result {
+                FindSuccessorResult::Successor(successor) => {
+                    assert_eq!(successor.id, expected, "lookup of {} from {}", id, start);
+                    return;
+                }
+                FindSuccessorResult::ClosestPrecedingNode(next) => {
+                    node = nodes
+                        .iter()
+                        .find(|node| node.id() == next.id)
+                        .unwrap()
+                        .clone();
+                }
+            }
+        }
+        panic!(
+            "iterative lookup of {} from {} did not terminate",
+            id, start
+        );
+    }
+
+    #[tokio::test]
+    async fn test_single_node_ring() {
+        let network = TestNetwork::new();
+        let nodes = network.create_nodes(&ids(&[1000]), test_config(3));
+        let node = &nodes[0];
+        node.create().await;
+        assert_eq!(node.get_predecessor().await.unwrap(), None);
+        node.stabilize().await.unwrap();
+        node.fill_fingers().await.unwrap();
+        assert_eq!(node.get_predecessor().await.unwrap(), None);
+        assert_eq!(node.get_successors().await.unwrap(), vec![node.node_info()]);
+        for id in [0, 999, 1000, 1001, u128::MAX] {
+            assert_successor(&nodes, 0, id, 1000).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_node_ring() {
+        let network = TestNetwork::new();
+        let nodes = network.create_nodes(&ids(&[1000, 2000]), test_config(3));
+        nodes[0].create().await;
+        nodes[1].join(&*nodes[0]).await.unwrap();
+        stabilize(&network, &nodes, 2).await;
+        for node in &nodes {
+            node.fill_fingers().await.unwrap();
+        }
+        let (first, second) = (nodes[0].node_info(), nodes[1].node_info());
+        assert_eq!(
+            nodes[0].get_predecessor().await.unwrap(),
+            Some(second.clone())
+        );
+        assert_eq!(
+            nodes[1].get_predecessor().await.unwrap(),
+            Some(first.clone())
+        );
+        assert_eq!(nodes[0].get_successors().await.unwrap(), vec![second]);
+        assert_eq!(nodes[1].get_successors().await.unwrap(), vec![first]);
+        for start in 0..nodes.len() {
+            for id in [0, 999, 1000, 2001, u128::MAX] {
+                assert_successor(&nodes, start, id, 1000).await;
+            }
+            for id in [1001, 1999, 2000] {
+                assert_successor(&nodes, start, id, 2000).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookup_key() {
+        let network = TestNetwork::new();
+        let nodes = create_ring(&network, test_config(3)).await;
+        for key in ["foo", "bar", "baz"] {
+            let id = HashAlgorithm::default().hash(key.as_bytes());
+            let result = nodes[3].lookup_key(key.as_bytes()).await.unwrap();
+            assert_eq!(
+                result.successor.id,
+                expected_successor(&network, &nodes, id)
+            );
+        }
+    }
+
+    /// Creates a ring whose nodes are hosted by the same process, but have no fingers, so that
+    /// lookups follow the successor lists.
+    async fn create_ring_without_fingers(
+        network: &Arc<TestNetwork>,
+        config: Config,
+    ) -> Vec<Arc<DynLocalNode>> {
+        let ids: Vec<_> = (0..NODE_COUNT)
+            .map(|i| ChordId::from(i * (u128::MAX / NODE_COUNT)))
+            .collect();
+        let nodes = network.create_nodes(&ids, config);
+        nodes[0].create().await;
+        for (i, node) in nodes.iter().enumerate().skip(1) {
+            node.join(&*nodes[0]).await.unwrap();
+            stabilize(network, &nodes[..=i], 2).await;
+        }
+        stabilize(network, &nodes, 2).await;
+        nodes
+    }
+
+    fn config_without_cache(max_hops: u32) -> Config {
+        Config {
+            lookup_config: LookupConfig { max_hops },
+            routing_cache_config: RoutingCacheConfig {
+                enabled: false,
+                ..Default::default()
+            },
+            ..test_config(3)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recursive_and_iterative_lookups() {
+        let network = TestNetwork::new();
+        let config = config_without_cache(LookupConfig::default().max_hops);
+        let nodes = create_ring_without_fingers(&network, config.clone()).await;
+        // The lookup follows the successor lists from 5 to 8 and 1
+        let id = nodes[2].id() - ChordId::from(1u128);
+
+        let outcome = nodes[5]
+            .find_successor(FindSuccessorParameters {
+                id,
+                iterate: true,
+                hops: 0,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome.result,
+            FindSuccessorResult::ClosestPrecedingNode(nodes[8].node_info())
+        );
+        assert_eq!(outcome.hops, 0);
+        let outcome = nodes[5]
+            .find_successor(FindSuccessorParameters {
+                id,
+                iterate: false,
+                hops: 0,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome.result,
+            FindSuccessorResult::Successor(nodes[2].node_info())
+        );
+        assert_eq!(outcome.hops, 2);
+
+        let lookup_driver: Arc<dyn LookupDriver> = network.create_process(config).resolve();
+        for iterate in [false, true] {
+            let result = lookup_driver
+                .find_successor(&*nodes[5], id, iterate)
+                .await
+                .unwrap();
+            assert_eq!(
+                result,
+                LookupResult {
+                    successor: nodes[2].node_info(),
+                    hops: 2
+                },
+                "iterate: {}",
+                iterate
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookups_respect_max_hops() {
+        let network = TestNetwork::new();
+        let config = config_without_cache(1);
+        let nodes = create_ring_without_fingers(&network, config.clone()).await;
+        let id = nodes[2].id() - ChordId::from(1u128);
+        let result = nodes[5]
+            .find_successor(FindSuccessorParameters {
+                id,
+                iterate: false,
+                hops: 0,
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(NodeError::MaxHopsExceeded { max_hops: 1, .. })
+        ));
+
+        let lookup_driver: Arc<dyn LookupDriver> = network.create_process(config).resolve();
+        for iterate in [false, true] {
+            let result = lookup_driver.find_successor(&*nodes[5], id, iterate).await;
+            assert!(
+                matches!(result, Err(NodeError::MaxHopsExceeded { max_hops: 1, .. })),
+                "iterate: {}",
+                iterate
+            );
+        }
+        // A lookup that needs a single hop succeeds
+        let result = lookup_driver
+            .find_successor(&*nodes[5], nodes[9].id(), true)
+            .await
+            .unwrap();
+        assert_eq!(result.successor, nodes[9].node_info());
+        assert_eq!(result.hops, 1);
+    }
+
+    #[tokio::test]
+    async fn test_routing_cache_shortens_lookups() {
+        for (enabled, expected_hops) in [(false, 2), (true, 1)] {
+            let network = TestNetwork::new();
+            let config = Config {
+                routing_cache_config: RoutingCacheConfig {
+                    enabled,
+                    ..Default::default()
+                },
+                ..test_config(3)
+            };
+            let nodes = create_ring_without_fingers(&network, config).await;
+            // Without the cache, the lookup follows the successor lists from 5 to 8 and 1
+            let id = nodes[2].id() - ChordId::from(1u128);
+            let outcome = nodes[5]
+                .find_successor(FindSuccessorParameters {
+                    id,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(
+                outcome.result,
+                FindSuccessorResult::Successor(nodes[2].node_info())
+            );
+            assert_eq!(outcome.hops, expected_hops, "enabled: {}", enabled);
+            assert_lookups_succeed(&network, &nodes).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_require_ownership() {
+        let network = TestNetwork::new();
+        let nodes = create_ring(&network, test_config(3)).await;
+        let key = b"foo".to_vec();
+        let owner = expected_successor(&network, &nodes, HashAlgorithm::default().hash(&key));
+        for node in &nodes {
+            let result = node.put(key.clone(), b"bar".to_vec(), None).await;
+            if node.id() == owner {
+                result.unwrap();
+                assert_eq!(
+                    node.get(key.clone(), None).await.unwrap(),
+                    Some(b"bar".to_vec())
+                );
+                assert!(node.delete(key.clone()).await.unwrap());
+            } else {
+                assert!(
+                    matches!(result, Err(NodeError::NotOwner { .. })),
+                    "{:?}",
+                    result
+                );
+                assert!(matches!(
+                    node.delete(key.clone()).await,
+                    Err(NodeError::NotOwner { .. })
+                ));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_join_requires_same_hash_algorithm() {
+        let network = TestNetwork::new();
+        let seed = network.create_nodes(&ids(&[1000]), test_config(3));
+        seed[0].create().await;
+        let config = Config {
+            hash_algorithm: HashAlgorithm::XxHash,
+            ..test_config(3)
+        };
+        let node = network.create_nodes(&ids(&[2000]), config);
+        let result = node[0].join(&*seed[0]).await;
+        assert!(
+            matches!(result, Err(NodeError::IncompatibleRing { .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    fn handoff_config() -> Config {
+        Config {
+            handoff_config: HandoffConfig {
+                batch_size: 4,
+                batch_delay: Duration::ZERO,
+            },
+            ..test_config(3)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_join_takes_over_keys() {
+        let network = TestNetwork::new();
+        let mut nodes = network.create_nodes(&ids(&[0, u128::MAX / 2]), handoff_config());
+        nodes[0].create().await;
+        nodes[1].join(&*nodes[0]).await.unwrap();
+        stabilize(&network, &nodes, 2).await;
+        let keys: Vec<_> = (0..50).map(|i| format!("key-{}", i).into_bytes()).collect();
+        for key in &keys {
+            let owner = expected_successor(&network, &nodes, HashAlgorithm::default().hash(key));
+            let owner = nodes.iter().find(|node| node.id() == owner).unwrap();
+            owner.put(key.clone(), key.clone(), None).await.unwrap();
+        }
+
+        // The new node takes over the keys in (0, 2^126] from the node at 2^127
+        let joined = network.create_nodes(&ids(&[u128::MAX / 4]), handoff_config());
+        joined[0].join(&*nodes[0]).await.unwrap();
+        nodes.insert(1, joined[0].clone());
+        stabilize(&network, &nodes, 3).await;
+        for key in &keys {
+            let owner = expected_successor(&network, &nodes, HashAlgorithm::default().hash(key));
+            for node in &nodes {
+                let stored = node.get(key.clone(), None).await.unwrap();
+                if node.id() == owner {
+                    assert_eq!(stored.as_ref(), Some(key));
+                } else {
+                    assert_eq!(stored, None);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_range_resumes() {
+        let network = TestNetwork::new();
+        let node = network.create_nodes(&ids(&[1000]), handoff_config()).remove(0);
+        node.create().await;
+        let mut keys: Vec<_> = (0..10).map(|i| format!("key-{}", i).into_bytes()).collect();
+        for key in &keys {
+            node.put(key.clone(), key.clone(), None).await.unwrap();
+        }
+        keys.sort();
+        let parameters = |resume_after: Option<Vec<u8>>| TransferRangeParameters {
+            start: node.id(),
+            end: node.id(),
+            resume_after,
+            remove: true,
+        };
+
+        // The transfer is interrupted after the first batch
+        let first = node
+            .transfer_range(parameters(None))
+            .await
+            .unwrap()
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        let first: Vec<_> = first.into_iter().map(|replica| replica.key).collect();
+        assert_eq!(first, keys[..4]);
+
+        let rest: Vec<_> = node
+            .transfer_range(parameters(first.last().cloned()))
+            .await
+            .unwrap()
+            .map(|batch| batch.unwrap())
+            .concat()
+            .await
+            .into_iter()
+            .map(|replica| replica.key)
+            .collect();
+        assert_eq!(rest, keys[4..]);
+        // The acknowledged entries were removed
+        for key in &keys[..4] {
+            assert_eq!(node.get(key.clone(), None).await.unwrap(), None);
+        }
+        assert_eq!(
+            node.get(keys[4].clone(), None).await.unwrap().as_ref(),
+            Some(&keys[4])
+        );
+    }
+}
@@ -6,20 +6,47 @@
  * https://opensource.org/licenses/MIT.
  */
 
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 
+use async_trait::async_trait;
 use cached::{Cached, SizedCache};
+use http::Uri;
 use shaku::{Component, Interface};
 use tonic::transport::Channel;
 
 use chord_types::node_info::NodeInfo;
 
 use crate::config::ConfigProvider;
-use crate::node::BoxedNode;
-use crate::node_grpc_client::NodeGrpcClient;
+use crate::node::{DynNode, NodeError};
+use crate::node_grpc_client::{get_hosted_nodes, NodeChannel, NodeGrpcClient};
+use crate::node_registry::NodeRegistry;
 
+#[async_trait]
 pub trait NodeClientFactory: Interface {
-    fn create_node_client(&self, node_info: &NodeInfo) -> BoxedNode;
+    /// Creates a client for the node, which connects to the endpoints of the node in order of
+    /// preference.
+    ///
+    /// Nodes hosted by this process are returned directly, so calls to them do not leave the
+    /// process.
+    fn create_node_client(&self, node_info: &NodeInfo) -> Arc<DynNode>;
+
+    /// Asks the process listening on `address` which nodes it hosts.
+    ///
+    /// This is used to find an entry point into an existing ring, where only the address of a
+    /// process is known, but not the ids of its nodes.
+    async fn get_hosted_nodes(&self, address: SocketAddr) -> Result<HostedNodes, NodeError>;
+}
+
+/// The nodes hosted by a process, as reported by the process itself.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HostedNodes {
+    pub nodes: Vec<NodeInfo>,
+    /// The capacity of the process relative to the other processes of the ring, if it reports
+    /// one.
+    pub capacity: Option<f64>,
+    /// The number of keys the nodes own.
+    pub key_count: usize,
 }
 
 #[derive(Component)]
@@ -28,36 +55,107 @@ pub struct GrpcNodeClientFactory {
     #[shaku(inject)]
     config_provider: Arc<dyn ConfigProvider>,
 
+    #[shaku(inject)]
+    registry: Arc<dyn NodeRegistry>,
+
+    /// Keyed by the endpoints as well, so a node that is reachable elsewhere gets a new channel.
     #[shaku(default)]
-    channels: OnceLock<Mutex<SizedCache<u64, Channel>>>,
+    channels: OnceLock<Mutex<SizedCache<NodeInfo, NodeChannel>>>,
 }
 
 impl GrpcNodeClientFactory {
-    fn channels(&self) -> MutexGuard<SizedCache<u64, Channel>> {
+    fn channels(&self) -> MutexGuard<'_, SizedCache<NodeInfo, NodeChannel>> {
         self.channels
             .get_or_init(|| Mutex::new(SizedCache::with_size(1024)))
             .lock()
             .unwrap()
     }
 
-    fn get_channel(&self, node_info: &NodeInfo) -> Channel {
+    fn get_channel(&self, node_info: &NodeInfo) -> NodeChannel {
+        let config = &self.config_provider.get_config().client_config;
         let mut channels = self.channels();
         channels
-            .cache_get_or_set_with(node_info.id, || {
-                let ref config = self.config_provider.get_config().client_config;
-                Channel::builder(node_info.uri())
-                    .connect_timeout(config.connect_timeout)
-                    .timeout(config.request_timeout)
-                    .keep_alive_timeout(config.keep_alive_timeout)
-                    .connect_lazy()
-            })
+            .cache_get_or_set_with(node_info.clone(), || NodeChannel::new(node_info, config))
             .clone()
     }
+
+    fn create_channel(&self, uri: Uri) -> Channel {
+        let config = &self.config_provider.get_config().client_config;
+        Channel::builder(uri)
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout)
+            .keep_alive_timeout(config.keep_alive_timeout)
+            .connect_lazy()
+    }
 }
 
+#[async_trait]
 impl NodeClientFactory for GrpcNodeClientFactory {
-    fn create_node_client(&self, node_info: &NodeInfo) -> BoxedNode {
+    fn create_node_client(&self, node_info: &NodeInfo) -> Arc<DynNode> {
+        if let Some(node) = self.registry.get_node(node_info.id) {
+            return node;
+        }
         let channel = self.get_channel(node_info);
-        Box::new(NodeGrpcClient::new(*node_info, channel))
+        Arc::new(NodeGrpcClient::new(node_info.clone(), channel))
+    }
+
+    async fn get_hosted_nodes(&self, address: SocketAddr) -> Result<HostedNodes, NodeError> {
+        let uri = Uri::builder()
+            .scheme("http")
+            .authority(address.to_string())
+            .path_and_query("/")
+            .build()
+            .unwrap();
+        get_hosted_nodes(self.create_channel(uri)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shaku::{module, HasComponent};
+
+    use chord_types::chord_id::ChordId;
+
+    use crate::config::{Config, DefaultConfigProvider, DefaultConfigProviderParameters};
+    use crate::node_registry::DefaultNodeRegistry;
+    use crate::testing::TestNetwork;
+
+    use super::*;
+
+    module! {
+        ClientProgram {
+            components = [DefaultConfigProvider, DefaultNodeRegistry, GrpcNodeClientFactory],
+            providers = []
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_nodes_are_called_directly() {
+        let ids = [ChordId::from(1u128), ChordId::from(2u128)];
+        // Neither node is reachable over the network
+        let nodes = TestNetwork::new().create_nodes(&ids, Config::default());
+        nodes[0].create().await;
+        let program = ClientProgram::builder()
+            .with_component_parameters::<DefaultConfigProvider>(DefaultConfigProviderParameters {
+                config: Arc::new(Config::default()),
+            })
+            .build();
+        let registry: Arc<dyn NodeRegistry> = program.resolve();
+        registry.register(nodes[0].clone());
+        let factory: Arc<dyn NodeClientFactory> = program.resolve();
+
+        let client = factory.create_node_client(&nodes[0].node_info());
+        assert!(Arc::ptr_eq(&client, &(nodes[0].clone() as Arc<DynNode>)));
+        client.ping().await.unwrap();
+        assert_eq!(
+            client.get_successors().await.unwrap(),
+            vec![nodes[0].node_info()]
+        );
+
+        let client = factory.create_node_client(&nodes[1].node_info());
+        assert!(matches!(
+            client.ping().await,
+            Err(NodeError::Unreachable(_))
+        ));
     }
 }
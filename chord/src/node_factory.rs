@@ -6,15 +6,40 @@
  * https://opensource.org/licenses/MIT.
  */
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use shaku::{Component, Interface};
 
-use crate::node::{BoxedNode, NodeImpl};
+use chord_types::chord_id::ChordId;
+use chord_types::hashing::{HashAlgorithm, KeyHasher};
+use chord_types::node_info::{Endpoint, NodeInfo};
+
+use crate::config::ConfigProvider;
+use crate::failure_detector::FailureDetector;
+use crate::lookup_driver::LookupDriver;
+use crate::node::{BoxedLocalNode, NodeError, NodeImpl};
 use crate::node_client_factory::NodeClientFactory;
+use crate::routing_cache::RoutingCache;
+use crate::storage::disk::DiskStorage;
+use crate::storage::memory::MemoryStorage;
+use crate::storage::BoxedStorage;
 
 pub trait NodeFactory: Interface {
-    fn create_node(&self, id: u64) -> BoxedNode;
+    /// The id of the virtual node with the given index.
+    ///
+    /// It is either pinned in the config, or derived from the advertised address and `index`.
+    fn virtual_node_id(&self, index: u32) -> Result<ChordId, NodeError>;
+
+    /// Creates the virtual node with the given index, see [NodeFactory::virtual_node_id].
+    fn create_virtual_node(&self, index: u32) -> Result<BoxedLocalNode, NodeError> {
+        self.create_node(self.virtual_node_id(index)?)
+    }
+
+    /// Creates a node with the given id.
+    ///
+    /// If a data directory is configured, the node recovers the entries it stored there before.
+    fn create_node(&self, id: ChordId) -> Result<BoxedLocalNode, NodeError>;
 }
 
 #[derive(Component)]
@@ -22,13 +47,147 @@ pub trait NodeFactory: Interface {
 pub struct DefaultNodeFactory {
     #[shaku(inject)]
     client_factory: Arc<dyn NodeClientFactory>,
+
+    #[shaku(inject)]
+    lookup_driver: Arc<dyn LookupDriver>,
+
+    #[shaku(inject)]
+    failure_detector: Arc<dyn FailureDetector>,
+
+    #[shaku(inject)]
+    routing_cache: Arc<dyn RoutingCache>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+impl DefaultNodeFactory {
+    /// The configured advertised address, unless it is unspecified.
+    fn advertised_address(&self) -> Option<SocketAddr> {
+        self.config_provider
+            .get_config()
+            .advertised_address
+            .filter(is_specified)
+    }
+
+    fn create_storage(&self, id: ChordId) -> Result<BoxedStorage, NodeError> {
+        let config = self.config_provider.get_config();
+        Ok(match &config.storage_config.data_dir {
+            Some(data_dir) => Box::new(DiskStorage::open(
+                data_dir.join(id.to_string()),
+                &config.storage_config,
+            )?),
+            None => Box::new(MemoryStorage::default()),
+        })
+    }
 }
 
 impl NodeFactory for DefaultNodeFactory {
-    /// Create a new node with a random id.
-    ///
-    /// Uses [rand::thread_rng] to generate a random id.
-    fn create_node(&self, id: u64) -> BoxedNode {
-        Box::new(NodeImpl::new(id, self.client_factory.clone()))
+    fn virtual_node_id(&self, index: u32) -> Result<ChordId, NodeError> {
+        let config = self.config_provider.get_config();
+        Ok(match config.node_id_config.pinned_ids.get(index as usize) {
+            Some(&id) => id,
+            None => {
+                let address = self
+                    .advertised_address()
+                    .ok_or(NodeError::no_advertised_address(index))?;
+                derive_node_id(config.hash_algorithm, address, index)
+            }
+        })
+    }
+
+    fn create_node(&self, id: ChordId) -> Result<BoxedLocalNode, NodeError> {
+        let mut endpoints: Vec<_> = self
+            .advertised_address()
+            .map(Endpoint::from)
+            .into_iter()
+            .collect();
+        for endpoint in &self.config_provider.get_config().advertised_endpoints {
+            if !endpoints.contains(endpoint) {
+                endpoints.push(endpoint.clone());
+            }
+        }
+        Ok(Box::new(NodeImpl::new(
+            NodeInfo::new(id, endpoints),
+            self.client_factory.clone(),
+            self.lookup_driver.clone(),
+            self.failure_detector.clone(),
+            self.routing_cache.clone(),
+            self.create_storage(id)?,
+            self.config_provider.clone(),
+        )))
+    }
+}
+
+/// Whether `address` names a single host and port, unlike e.g. `0.0.0.0:0`.
+///
+/// Ids derived from an unspecified address would be the same on every host.
+pub fn is_specified(address: &SocketAddr) -> bool {
+    !address.ip().is_unspecified() && address.port() != 0
+}
+
+/// Derives the id of a virtual node from the address it is reachable under and its index.
+pub fn derive_node_id(algorithm: HashAlgorithm, address: SocketAddr, index: u32) -> ChordId {
+    algorithm.hash(format!("{}#{}", address, index).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use shaku::HasComponent;
+
+    use crate::config::{Config, NodeIdConfig};
+    use crate::testing::TestNetwork;
+
+    use super::*;
+
+    #[test]
+    fn test_derive_node_id() {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, 7001));
+        let id = derive_node_id(HashAlgorithm::Sha1, address, 0);
+        assert_eq!(id, derive_node_id(HashAlgorithm::Sha1, address, 0));
+        assert_ne!(id, derive_node_id(HashAlgorithm::Sha1, address, 1));
+        assert_ne!(id, derive_node_id(HashAlgorithm::Sha256, address, 0));
+        let other_port = SocketAddr::from((Ipv4Addr::LOCALHOST, 7002));
+        assert_ne!(id, derive_node_id(HashAlgorithm::Sha1, other_port, 0));
+    }
+
+    #[test]
+    fn test_ids_require_a_specific_address() {
+        let network = TestNetwork::new();
+        for (address, specified) in [
+            (SocketAddr::from((Ipv4Addr::LOCALHOST, 7001)), true),
+            (SocketAddr::from((Ipv4Addr::UNSPECIFIED, 7001)), false),
+            (SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), false),
+        ] {
+            assert_eq!(is_specified(&address), specified, "{}", address);
+            let factory: Arc<dyn NodeFactory> = network
+                .create_process(Config {
+                    advertised_address: Some(address),
+                    ..Default::default()
+                })
+                .resolve();
+            let result = factory.create_virtual_node(0);
+            assert_eq!(result.is_ok(), specified, "{}", address);
+        }
+        let factory: Arc<dyn NodeFactory> = network.create_process(Config::default()).resolve();
+        assert!(matches!(
+            factory.create_virtual_node(0),
+            Err(NodeError::NoAdvertisedAddress(0))
+        ));
+        // Pinned ids need no address
+        let factory: Arc<dyn NodeFactory> = network
+            .create_process(Config {
+                node_id_config: NodeIdConfig {
+                    pinned_ids: vec![ChordId::from(1u128)],
+                },
+                ..Default::default()
+            })
+            .resolve();
+        assert_eq!(
+            factory.create_virtual_node(0).unwrap().id(),
+            ChordId::from(1u128)
+        );
     }
 }
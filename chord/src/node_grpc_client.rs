@@ -6,58 +6,168 @@
  * https://opensource.org/licenses/MIT.
  */
 
+use std::sync::{Arc, Mutex};
+
 use async_trait::async_trait;
-use tonic::Request;
-use tonic::transport::Channel;
+use futures::StreamExt;
+use tonic::{Code, Request, Status};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
 
-use chord_types::node_info::NodeInfo;
+use chord_types::chord_id::ChordId;
+use chord_types::node_info::{Host, NodeInfo, Scheme};
 
 use crate::api::com::barmetler::chord::{
-    find_successor_response, FindSuccessorRequest, GetPredecessorRequest,
+    find_successor_response, DeleteRequest, DropReplicasRequest, FindSuccessorRequest,
+    GetNodesRequest, GetPredecessorRequest, GetPredecessorsRequest, GetReplicaRequest, GetRequest,
+    GetRingParametersRequest, GetSuccessorsRequest, HashAlgorithm, NotifyRequest, PingRequest,
+    PredecessorLeavingRequest, PutRequest, ReplicateRequest, SuccessorLeavingRequest,
+    TransferRangeRequest,
 };
 use crate::api::com::barmetler::chord::node_service_client::NodeServiceClient;
-use crate::convert::TryToDomain;
-use crate::node::{FindSuccessorParameters, FindSuccessorResult, Node, NodeError};
+use crate::config::{ClientConfig, Consistency};
+use crate::convert::{ConversionError, ToDomain, ToProto, TryToDomain};
+use crate::node::{
+    FindSuccessorOutcome, FindSuccessorParameters, FindSuccessorResult, Node, NodeError,
+    RingParameters, TransferRangeParameters, TransferStream,
+};
+use crate::node_client_factory::HostedNodes;
+use crate::replication::Replica;
+
+/// A channel to a node that is connected on first use.
+///
+/// The endpoints of the node are tried in order of preference, and the first one that accepts the
+/// connection is used until it fails. If none does, the next use tries all of them again.
+#[derive(Clone)]
+pub struct NodeChannel {
+    id: ChordId,
+    endpoints: Arc<Vec<Endpoint>>,
+    channel: Arc<Mutex<Option<Channel>>>,
+}
+
+impl NodeChannel {
+    pub fn new(node_info: &NodeInfo, config: &ClientConfig) -> Self {
+        let endpoints = node_info
+            .endpoints
+            .iter()
+            .filter_map(|endpoint| {
+                let mut builder = Channel::builder(endpoint.uri())
+                    .connect_timeout(config.connect_timeout)
+                    .timeout(config.request_timeout)
+                    .keep_alive_timeout(config.keep_alive_timeout);
+                if endpoint.scheme == Scheme::Https {
+                    let mut tls_config = ClientTlsConfig::new();
+                    if let Host::Dns(name) = &endpoint.host {
+                        tls_config = tls_config.domain_name(name);
+                    }
+                    builder = builder
+                        .tls_config(tls_config)
+                        .inspect_err(|e| log::warn!("cannot use endpoint {}: {}", endpoint, e))
+                        .ok()?;
+                }
+                Some(builder)
+            })
+            .collect();
+        Self {
+            id: node_info.id,
+            endpoints: Arc::new(endpoints),
+            channel: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub async fn get(&self) -> Result<Channel, NodeError> {
+        if let Some(channel) = self.channel.lock().unwrap().clone() {
+            return Ok(channel);
+        }
+        for endpoint in self.endpoints.iter() {
+            match endpoint.connect().await {
+                Ok(channel) => {
+                    *self.channel.lock().unwrap() = Some(channel.clone());
+                    return Ok(channel);
+                }
+                Err(e) => {
+                    log::debug!("failed to connect to {}: {}", endpoint.uri(), e);
+                }
+            }
+        }
+        Err(NodeError::unreachable(self.id))
+    }
+
+    /// Drops the connection, so that the next use tries all endpoints again.
+    pub fn reset(&self) {
+        self.channel.lock().unwrap().take();
+    }
+}
 
 pub struct NodeGrpcClient {
     node_info: NodeInfo,
-    channel: Channel,
+    channel: NodeChannel,
 }
 
 impl NodeGrpcClient {
-    pub fn new(node_info: NodeInfo, channel: Channel) -> Self {
-        Self {
-            node_info: node_info.to_owned(),
-            channel,
-        }
+    pub fn new(node_info: NodeInfo, channel: NodeChannel) -> Self {
+        Self { node_info, channel }
     }
 }
 
 impl NodeGrpcClient {
-    fn client(&self) -> NodeServiceClient<Channel> {
-        NodeServiceClient::new(self.channel.clone())
+    async fn client(&self) -> Result<NodeServiceClient<Channel>, NodeError> {
+        Ok(NodeServiceClient::new(self.channel.get().await?))
+    }
+
+    /// Fails a request, and drops the connection if the node could not be reached over it.
+    fn failed(&self, status: Status) -> NodeError {
+        if status.code() == Code::Unavailable {
+            self.channel.reset();
+        }
+        status.into()
     }
 }
 
+/// Lists the nodes hosted by the process on the other end of `channel`.
+pub async fn get_hosted_nodes(channel: Channel) -> Result<HostedNodes, NodeError> {
+    let response = NodeServiceClient::new(channel)
+        .get_nodes(Request::new(GetNodesRequest {}))
+        .await?
+        .into_inner();
+    Ok(HostedNodes {
+        nodes: response
+            .nodes
+            .iter()
+            .map(|node| Ok(node.try_to_domain()?))
+            .collect::<Result<_, NodeError>>()?,
+        capacity: Some(response.capacity).filter(|&capacity| capacity > 0.0),
+        key_count: response.key_count as usize,
+    })
+}
+
 #[async_trait]
 impl Node for NodeGrpcClient {
-    fn id(&self) -> u64 {
+    fn id(&self) -> ChordId {
         self.node_info.id
     }
 
+    fn node_info(&self) -> NodeInfo {
+        self.node_info.clone()
+    }
+
     async fn find_successor(
         &self,
-        FindSuccessorParameters { id, .. }: FindSuccessorParameters,
-    ) -> Result<FindSuccessorResult, NodeError> {
-        Ok(
-            match self
-                .client()
-                .find_successor(Request::new(FindSuccessorRequest {
-                    node_id: self.node_info.id.to_string(),
-                    id: id.to_string(),
-                }))
-                .await?
-                .into_inner()
+        FindSuccessorParameters { id, iterate, hops }: FindSuccessorParameters,
+    ) -> Result<FindSuccessorOutcome, NodeError> {
+        let response = self
+            .client()
+            .await?
+            .find_successor(Request::new(FindSuccessorRequest {
+                node_id: self.node_info.id.to_string(),
+                id: id.to_string(),
+                iterate,
+                hops,
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner();
+        Ok(FindSuccessorOutcome {
+            result: match response
                 .node
                 .ok_or(NodeError::invalid_response(self.node_info.id))?
             {
@@ -68,19 +178,251 @@ impl Node for NodeGrpcClient {
                     FindSuccessorResult::ClosestPrecedingNode(node.try_to_domain()?)
                 }
             },
-        )
+            hops: response.hops,
+        })
     }
 
-    async fn get_predecessor(&self) -> Result<NodeInfo, NodeError> {
+    async fn get_predecessor(&self) -> Result<Option<NodeInfo>, NodeError> {
         Ok(self
             .client()
+            .await?
             .get_predecessor(Request::new(GetPredecessorRequest {
                 node_id: self.node_info.id.to_string(),
             }))
-            .await?
+            .await
+            .map_err(|status| self.failed(status))?
             .into_inner()
             .node
-            .ok_or(NodeError::invalid_response(self.node_info.id))?
-            .try_to_domain()?)
+            .map(|node| node.try_to_domain())
+            .transpose()?)
+    }
+
+    async fn get_successors(&self) -> Result<Vec<NodeInfo>, NodeError> {
+        self.client()
+            .await?
+            .get_successors(Request::new(GetSuccessorsRequest {
+                node_id: self.node_info.id.to_string(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner()
+            .nodes
+            .iter()
+            .map(|node| Ok(node.try_to_domain()?))
+            .collect()
+    }
+
+    async fn get_predecessors(&self) -> Result<Vec<NodeInfo>, NodeError> {
+        self.client()
+            .await?
+            .get_predecessors(Request::new(GetPredecessorsRequest {
+                node_id: self.node_info.id.to_string(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner()
+            .nodes
+            .iter()
+            .map(|node| Ok(node.try_to_domain()?))
+            .collect()
+    }
+
+    async fn ping(&self) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .ping(Request::new(PingRequest {
+                node_id: self.node_info.id.to_string(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn get_ring_parameters(&self) -> Result<RingParameters, NodeError> {
+        let response = self
+            .client()
+            .await?
+            .get_ring_parameters(Request::new(GetRingParametersRequest {
+                node_id: self.node_info.id.to_string(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner();
+        let hash_algorithm = HashAlgorithm::try_from(response.hash_algorithm).map_err(|_| {
+            ConversionError::ConversionFailed(format!(
+                "unknown hash algorithm {}",
+                response.hash_algorithm
+            ))
+        })?;
+        Ok(RingParameters {
+            id_bits: response.id_bits,
+            hash_algorithm: hash_algorithm.to_domain(),
+        })
+    }
+
+    async fn notify(&self, node: NodeInfo) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .notify(Request::new(NotifyRequest {
+                node_id: self.node_info.id.to_string(),
+                node: Some(node.to_proto()),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn successor_leaving(
+        &self,
+        leaving: NodeInfo,
+        successors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .successor_leaving(Request::new(SuccessorLeavingRequest {
+                node_id: self.node_info.id.to_string(),
+                leaving: Some(leaving.to_proto()),
+                successors: successors.iter().map(ToProto::to_proto).collect(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn predecessor_leaving(
+        &self,
+        leaving: NodeInfo,
+        predecessors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .predecessor_leaving(Request::new(PredecessorLeavingRequest {
+                node_id: self.node_info.id.to_string(),
+                leaving: Some(leaving.to_proto()),
+                predecessors: predecessors.iter().map(ToProto::to_proto).collect(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        key: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<Option<Vec<u8>>, NodeError> {
+        Ok(self
+            .client()
+            .await?
+            .get(Request::new(GetRequest {
+                node_id: self.node_info.id.to_string(),
+                key,
+                consistency: consistency.as_ref().map(ToProto::to_proto),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner()
+            .value)
+    }
+
+    async fn put(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .put(Request::new(PutRequest {
+                node_id: self.node_info.id.to_string(),
+                key,
+                value,
+                consistency: consistency.as_ref().map(ToProto::to_proto),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: Vec<u8>) -> Result<bool, NodeError> {
+        Ok(self
+            .client()
+            .await?
+            .delete(Request::new(DeleteRequest {
+                node_id: self.node_info.id.to_string(),
+                key,
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner()
+            .existed)
+    }
+
+    async fn get_replica(&self, key: Vec<u8>) -> Result<Option<Replica>, NodeError> {
+        Ok(self
+            .client()
+            .await?
+            .get_replica(Request::new(GetReplicaRequest {
+                node_id: self.node_info.id.to_string(),
+                key,
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner()
+            .replica
+            .as_ref()
+            .map(ToDomain::to_domain))
+    }
+
+    async fn replicate(&self, replicas: Vec<Replica>) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .replicate(Request::new(ReplicateRequest {
+                node_id: self.node_info.id.to_string(),
+                replicas: replicas.iter().map(ToProto::to_proto).collect(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn drop_replicas(&self, start: ChordId, end: ChordId) -> Result<(), NodeError> {
+        self.client()
+            .await?
+            .drop_replicas(Request::new(DropReplicasRequest {
+                node_id: self.node_info.id.to_string(),
+                start: start.to_string(),
+                end: end.to_string(),
+            }))
+            .await
+            .map_err(|status| self.failed(status))?;
+        Ok(())
+    }
+
+    async fn transfer_range(
+        &self,
+        TransferRangeParameters {
+            start,
+            end,
+            resume_after,
+            remove,
+        }: TransferRangeParameters,
+    ) -> Result<TransferStream, NodeError> {
+        let batches = self
+            .client()
+            .await?
+            .transfer_range(Request::new(TransferRangeRequest {
+                node_id: self.node_info.id.to_string(),
+                start: start.to_string(),
+                end: end.to_string(),
+                resume_after,
+                remove,
+            }))
+            .await
+            .map_err(|status| self.failed(status))?
+            .into_inner();
+        Ok(Box::pin(batches.map(|response| -> Result<Vec<Replica>, NodeError> {
+            Ok(response?.entries.iter().map(ToDomain::to_domain).collect())
+        })))
     }
 }
@@ -6,21 +6,37 @@
  * https://opensource.org/licenses/MIT.
  */
 
-use std::num::ParseIntError;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::StreamExt;
 use shaku::{Component, Interface};
 use thiserror::Error;
+use tonic::codegen::BoxStream;
 use tonic::{Code, Request, Response, Status};
 
+use chord_types::chord_id::{ChordId, ParseIdError};
+use chord_types::node_info::NodeInfo;
+
 use crate::api::com::barmetler::chord::{
-    find_successor_response, FindSuccessorRequest, FindSuccessorResponse, GetPredecessorRequest,
-    GetPredecessorResponse,
+    find_successor_response, DeleteRequest, DeleteResponse, DropReplicasRequest,
+    DropReplicasResponse, FindSuccessorRequest, FindSuccessorResponse, GetNodesRequest,
+    GetNodesResponse, GetPredecessorRequest, GetPredecessorResponse, GetPredecessorsRequest,
+    GetPredecessorsResponse, GetReplicaRequest, GetReplicaResponse, GetRequest, GetResponse,
+    GetRingParametersRequest, GetRingParametersResponse, GetSuccessorsRequest,
+    GetSuccessorsResponse, LookupKeyRequest, LookupKeyResponse, NotifyRequest, NotifyResponse,
+    PingRequest, PingResponse, PredecessorLeavingRequest, PredecessorLeavingResponse, PutRequest,
+    PutResponse, ReplicateRequest, ReplicateResponse, SuccessorLeavingRequest,
+    SuccessorLeavingResponse, TransferRangeRequest, TransferRangeResponse,
 };
 use crate::api::com::barmetler::chord::node_service_server::NodeService;
-use crate::convert::ToProto;
-use crate::node::{DynNode, FindSuccessorParameters, FindSuccessorResult, NodeError};
+use crate::api::com::barmetler::chord::Consistency as ConsistencyMsg;
+use crate::api::com::barmetler::chord::NodeInfo as NodeInfoMsg;
+use crate::config::{ConfigProvider, Consistency};
+use crate::convert::{ToDomain, ToProto, TryToDomain};
+use crate::node::{
+    DynNode, FindSuccessorParameters, FindSuccessorResult, NodeError, TransferRangeParameters,
+};
 use crate::node_manager::NodeManager;
 
 pub trait NodeGrpcServiceComponent: NodeService + Interface {}
@@ -30,14 +46,18 @@ pub trait NodeGrpcServiceComponent: NodeService + Interface {}
 pub struct NodeGrpcService {
     #[shaku(inject)]
     node_manager: Arc<dyn NodeManager>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
 }
 
 impl NodeGrpcServiceComponent for NodeGrpcService {}
 
 impl NodeGrpcService {
-    fn find_node(&self, id: u64) -> Result<Arc<DynNode>, NodeServiceError> {
+    fn find_node(&self, id: ChordId) -> Result<Arc<DynNode>, NodeServiceError> {
         self.node_manager
             .get_node(id)
+            .map(|node| node as Arc<DynNode>)
             .ok_or(NodeServiceError::node_not_found(id))
     }
 
@@ -59,15 +79,16 @@ impl NodeService for NodeGrpcService {
         let request = request.into_inner();
         let node = self.find_node_by_id_string(request.node_id)?;
         let id = id_from_string(request.id)?;
-        let result = node
+        let outcome = node
             .find_successor(FindSuccessorParameters {
                 id,
-                ..Default::default()
+                iterate: request.iterate,
+                hops: request.hops,
             })
             .await
             .map_err(NodeServiceError::from)?;
         Ok(Response::new(FindSuccessorResponse {
-            node: Some(match result {
+            node: Some(match outcome.result {
                 FindSuccessorResult::Successor(node) => {
                     find_successor_response::Node::Successor(node.to_proto())
                 }
@@ -75,6 +96,7 @@ impl NodeService for NodeGrpcService {
                     find_successor_response::Node::ClosestPrecedingNode(node.to_proto())
                 }
             }),
+            hops: outcome.hops,
         }))
     }
 
@@ -89,21 +111,257 @@ impl NodeService for NodeGrpcService {
             .await
             .map_err(NodeServiceError::from)?;
         Ok(Response::new(GetPredecessorResponse {
-            node: Some(node_info.to_proto()),
+            node: node_info.as_ref().map(ToProto::to_proto),
+        }))
+    }
+
+    async fn get_successors(
+        &self,
+        request: Request<GetSuccessorsRequest>,
+    ) -> Result<Response<GetSuccessorsResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let successors = node
+            .get_successors()
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(GetSuccessorsResponse {
+            nodes: successors.iter().map(ToProto::to_proto).collect(),
+        }))
+    }
+
+    async fn get_predecessors(
+        &self,
+        request: Request<GetPredecessorsRequest>,
+    ) -> Result<Response<GetPredecessorsResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let predecessors = node
+            .get_predecessors()
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(GetPredecessorsResponse {
+            nodes: predecessors.iter().map(ToProto::to_proto).collect(),
+        }))
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        node.ping().await.map_err(NodeServiceError::from)?;
+        Ok(Response::new(PingResponse {}))
+    }
+
+    async fn get_ring_parameters(
+        &self,
+        request: Request<GetRingParametersRequest>,
+    ) -> Result<Response<GetRingParametersResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let parameters = node
+            .get_ring_parameters()
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(GetRingParametersResponse {
+            id_bits: parameters.id_bits,
+            hash_algorithm: parameters.hash_algorithm.to_proto().into(),
+        }))
+    }
+
+    async fn notify(
+        &self,
+        request: Request<NotifyRequest>,
+    ) -> Result<Response<NotifyResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let node_info = node_info_from_proto(request.node.as_ref(), "node")?;
+        node.notify(node_info)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(NotifyResponse {}))
+    }
+
+    async fn successor_leaving(
+        &self,
+        request: Request<SuccessorLeavingRequest>,
+    ) -> Result<Response<SuccessorLeavingResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let leaving = node_info_from_proto(request.leaving.as_ref(), "leaving")?;
+        let successors = node_infos_from_proto(&request.successors)?;
+        node.successor_leaving(leaving, successors)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(SuccessorLeavingResponse {}))
+    }
+
+    async fn predecessor_leaving(
+        &self,
+        request: Request<PredecessorLeavingRequest>,
+    ) -> Result<Response<PredecessorLeavingResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let leaving = node_info_from_proto(request.leaving.as_ref(), "leaving")?;
+        let predecessors = node_infos_from_proto(&request.predecessors)?;
+        node.predecessor_leaving(leaving, predecessors)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(PredecessorLeavingResponse {}))
+    }
+
+    async fn get_nodes(
+        &self,
+        _request: Request<GetNodesRequest>,
+    ) -> Result<Response<GetNodesResponse>, Status> {
+        let key_count = self
+            .node_manager
+            .key_count()
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(GetNodesResponse {
+            nodes: self
+                .node_manager
+                .list_nodes()
+                .iter()
+                .map(|node| node.node_info().to_proto())
+                .collect(),
+            capacity: self.config_provider.get_config().rebalance_config.capacity,
+            key_count: key_count as u64,
+        }))
+    }
+
+    async fn lookup_key(
+        &self,
+        request: Request<LookupKeyRequest>,
+    ) -> Result<Response<LookupKeyResponse>, Status> {
+        let request = request.into_inner();
+        let id = id_from_string(request.node_id)?;
+        let node = self
+            .node_manager
+            .get_node(id)
+            .ok_or(NodeServiceError::node_not_found(id))?;
+        let result = node
+            .lookup_key(&request.key)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(LookupKeyResponse {
+            node: Some(result.successor.to_proto()),
+            hops: result.hops,
         }))
     }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let consistency = consistency_from_proto(request.consistency.as_ref())?;
+        let value = node
+            .get(request.key, consistency)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(GetResponse { value }))
+    }
+
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let consistency = consistency_from_proto(request.consistency.as_ref())?;
+        // The node rejects keys it is not responsible for
+        node.put(request.key, request.value, consistency)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(PutResponse {}))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let existed = node
+            .delete(request.key)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(DeleteResponse { existed }))
+    }
+
+    async fn get_replica(
+        &self,
+        request: Request<GetReplicaRequest>,
+    ) -> Result<Response<GetReplicaResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let replica = node
+            .get_replica(request.key)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(GetReplicaResponse {
+            replica: replica.as_ref().map(ToProto::to_proto),
+        }))
+    }
+
+    async fn replicate(
+        &self,
+        request: Request<ReplicateRequest>,
+    ) -> Result<Response<ReplicateResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        node.replicate(request.replicas.iter().map(ToDomain::to_domain).collect())
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(ReplicateResponse {}))
+    }
+
+    async fn drop_replicas(
+        &self,
+        request: Request<DropReplicasRequest>,
+    ) -> Result<Response<DropReplicasResponse>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        node.drop_replicas(id_from_string(request.start)?, id_from_string(request.end)?)
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(DropReplicasResponse {}))
+    }
+
+    async fn transfer_range(
+        &self,
+        request: Request<TransferRangeRequest>,
+    ) -> Result<Response<BoxStream<TransferRangeResponse>>, Status> {
+        let request = request.into_inner();
+        let node = self.find_node_by_id_string(request.node_id)?;
+        let batches = node
+            .transfer_range(TransferRangeParameters {
+                start: id_from_string(request.start)?,
+                end: id_from_string(request.end)?,
+                resume_after: request.resume_after,
+                remove: request.remove,
+            })
+            .await
+            .map_err(NodeServiceError::from)?;
+        Ok(Response::new(Box::pin(batches.map(
+            |batch| -> Result<TransferRangeResponse, Status> {
+                let batch = batch.map_err(NodeServiceError::from)?;
+                Ok(TransferRangeResponse {
+                    entries: batch.iter().map(ToProto::to_proto).collect(),
+                })
+            },
+        ))))
+    }
 }
 
 #[derive(Clone, Debug, Error)]
 pub enum NodeServiceError {
     #[error("node not found: {0}")]
-    NodeNotFound(u64),
+    NodeNotFound(ChordId),
     #[error("invalid id string: {id_string}")]
     InvalidIdString {
         id_string: String,
         #[source]
-        source: ParseIntError,
+        source: ParseIdError,
     },
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
     #[error(transparent)]
     NodeError(#[from] NodeError),
     #[error("unknown error")]
@@ -111,17 +369,21 @@ pub enum NodeServiceError {
 }
 
 impl NodeServiceError {
-    pub fn node_not_found(id: u64) -> Self {
+    pub fn node_not_found(id: ChordId) -> Self {
         NodeServiceError::NodeNotFound(id)
     }
 
-    pub fn invalid_id_string(id: impl Into<String>, source: ParseIntError) -> Self {
+    pub fn invalid_id_string(id: impl Into<String>, source: ParseIdError) -> Self {
         NodeServiceError::InvalidIdString {
             id_string: id.into(),
             source,
         }
     }
 
+    pub fn missing_field(field: &'static str) -> Self {
+        NodeServiceError::MissingField(field)
+    }
+
     pub fn unknown() -> Self {
         NodeServiceError::Unknown
     }
@@ -133,6 +395,7 @@ impl From<NodeServiceError> for Status {
         let code = match value {
             NodeServiceError::NodeNotFound(_) => Code::NotFound,
             NodeServiceError::InvalidIdString { .. } => Code::InvalidArgument,
+            NodeServiceError::MissingField(_) => Code::InvalidArgument,
             NodeServiceError::NodeError(node_error) => node_error.get_code(),
             NodeServiceError::Unknown => Code::Unknown,
         };
@@ -140,8 +403,34 @@ impl From<NodeServiceError> for Status {
     }
 }
 
-fn id_from_string(id: impl AsRef<str>) -> Result<u64, NodeServiceError> {
+pub(crate) fn id_from_string(id: impl AsRef<str>) -> Result<ChordId, NodeServiceError> {
     id.as_ref()
         .parse()
         .map_err(|e| NodeServiceError::invalid_id_string(id.as_ref(), e))
 }
+
+fn node_info_from_proto(
+    node_info: Option<&NodeInfoMsg>,
+    field: &'static str,
+) -> Result<NodeInfo, NodeServiceError> {
+    Ok(node_info
+        .ok_or(NodeServiceError::missing_field(field))?
+        .try_to_domain()
+        .map_err(NodeError::from)?)
+}
+
+fn node_infos_from_proto(node_infos: &[NodeInfoMsg]) -> Result<Vec<NodeInfo>, NodeServiceError> {
+    node_infos
+        .iter()
+        .map(|node_info| Ok(node_info.try_to_domain().map_err(NodeError::from)?))
+        .collect()
+}
+
+fn consistency_from_proto(
+    consistency: Option<&ConsistencyMsg>,
+) -> Result<Option<Consistency>, NodeServiceError> {
+    Ok(consistency
+        .map(TryToDomain::try_to_domain)
+        .transpose()
+        .map_err(NodeError::from)?)
+}
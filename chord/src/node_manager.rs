@@ -7,31 +7,259 @@
  */
 
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 
+use async_trait::async_trait;
+use log::{info, warn};
 use shaku::{Component, Interface};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tonic::Code;
 
-use crate::node::DynNode;
+use chord_types::chord_id::ChordId;
 
+use crate::maintenance::Maintenance;
+use crate::membership::Membership;
+use crate::node::{DynLocalNode, NodeError};
+use crate::node_factory::NodeFactory;
+use crate::node_registry::NodeRegistry;
+
+#[async_trait]
 pub trait NodeManager: Interface {
-    fn initialize(&self, nodes: HashMap<u64, Arc<DynNode>>);
+    /// Registers the virtual nodes created at startup, which have the indices `0..nodes.len()`.
+    fn initialize(&self, nodes: HashMap<ChordId, Arc<DynLocalNode>>);
+
+    fn get_node(&self, id: ChordId) -> Option<Arc<DynLocalNode>>;
+
+    fn list_nodes(&self) -> Vec<Arc<DynLocalNode>>;
+
+    /// Starts the maintenance of all nodes, including the ones added later, until `cancellation`
+    /// is triggered.
+    fn start_maintenance(&self, cancellation: CancellationToken);
+
+    /// Waits until the maintenance of all nodes has stopped.
+    async fn join_maintenance(&self);
+
+    /// Creates a virtual node, lets it join the ring through the existing nodes, and starts its
+    /// maintenance.
+    ///
+    /// Without an explicit id, the id is derived from the next virtual node index.
+    async fn add_node(&self, id: Option<ChordId>) -> Result<Arc<DynLocalNode>, NodeManagerError>;
+
+    /// Stops the maintenance of the node, lets it leave the ring gracefully, and removes it.
+    ///
+    /// The node is removed even if it could not hand over its neighbors.
+    async fn remove_node(&self, id: ChordId) -> Result<(), NodeManagerError>;
 
-    fn get_node(&self, id: u64) -> Option<Arc<DynNode>>;
+    /// The number of keys owned by the hosted nodes.
+    async fn key_count(&self) -> Result<usize, NodeError>;
 }
 
 #[derive(Component)]
 #[shaku(interface = NodeManager)]
 pub struct NodeManagerImpl {
+    #[shaku(inject)]
+    node_factory: Arc<dyn NodeFactory>,
+
+    #[shaku(inject)]
+    membership: Arc<dyn Membership>,
+
+    #[shaku(inject)]
+    maintenance: Arc<dyn Maintenance>,
+
+    #[shaku(inject)]
+    registry: Arc<dyn NodeRegistry>,
+
+    /// The index of the next virtual node without an explicit id.
+    #[shaku(default)]
+    next_index: AtomicU32,
+
     #[shaku(default)]
-    nodes: RwLock<HashMap<u64, Arc<DynNode>>>,
+    maintenance_cancellation: OnceLock<CancellationToken>,
+
+    #[shaku(default)]
+    maintenance_tasks: Mutex<HashMap<ChordId, (CancellationToken, JoinHandle<()>)>>,
+}
+
+impl NodeManagerImpl {
+    fn start_node_maintenance(&self, node: Arc<DynLocalNode>) {
+        let Some(cancellation) = self.maintenance_cancellation.get() else {
+            return;
+        };
+        let cancellation = cancellation.child_token();
+        let id = node.id();
+        let task = self.maintenance.start(node, cancellation.clone());
+        self.maintenance_tasks
+            .lock()
+            .unwrap()
+            .insert(id, (cancellation, task));
+    }
 }
 
+#[async_trait]
 impl NodeManager for NodeManagerImpl {
-    fn initialize(&self, nodes: HashMap<u64, Arc<DynNode>>) {
-        *self.nodes.write().unwrap() = nodes;
+    fn initialize(&self, nodes: HashMap<ChordId, Arc<DynLocalNode>>) {
+        self.next_index.store(nodes.len() as u32, Ordering::SeqCst);
+        for node in nodes.into_values() {
+            self.registry.register(node);
+        }
+    }
+
+    fn get_node(&self, id: ChordId) -> Option<Arc<DynLocalNode>> {
+        self.registry.get_node(id)
+    }
+
+    fn list_nodes(&self) -> Vec<Arc<DynLocalNode>> {
+        self.registry.list_nodes()
     }
 
-    fn get_node(&self, id: u64) -> Option<Arc<DynNode>> {
-        self.nodes.read().unwrap().get(&id).cloned()
+    fn start_maintenance(&self, cancellation: CancellationToken) {
+        if self.maintenance_cancellation.set(cancellation).is_err() {
+            return;
+        }
+        for node in self.list_nodes() {
+            self.start_node_maintenance(node);
+        }
+    }
+
+    async fn join_maintenance(&self) {
+        let tasks: Vec<_> = self.maintenance_tasks.lock().unwrap().drain().collect();
+        for (_, (_, task)) in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    async fn add_node(&self, id: Option<ChordId>) -> Result<Arc<DynLocalNode>, NodeManagerError> {
+        let id = match id {
+            Some(id) => id,
+            None => {
+                let index = self.next_index.fetch_add(1, Ordering::SeqCst);
+                self.node_factory.virtual_node_id(index)?
+            }
+        };
+        // Checked before the node opens its storage, which a live node with this id still uses
+        if self.get_node(id).is_some() {
+            return Err(NodeManagerError::already_exists(id));
+        }
+        let node: Arc<DynLocalNode> = Arc::from(self.node_factory.create_node(id)?);
+        let members = self.list_nodes();
+        self.membership.join_node(&*node, &members).await?;
+        // Register the node before it is known to others through stabilization
+        if !self.registry.register(node.clone()) {
+            // The same id was added concurrently
+            if let Err(e) = node.leave().await {
+                warn!(
+                    "Duplicate node {} failed to leave the ring: {}",
+                    node.id(),
+                    e
+                );
+            }
+            return Err(NodeManagerError::already_exists(node.id()));
+        }
+        self.start_node_maintenance(node.clone());
+        info!("Added node {}", node.id());
+        Ok(node)
+    }
+
+    async fn remove_node(&self, id: ChordId) -> Result<(), NodeManagerError> {
+        let node = self.get_node(id).ok_or(NodeManagerError::not_found(id))?;
+        let task = self.maintenance_tasks.lock().unwrap().remove(&id);
+        if let Some((cancellation, task)) = task {
+            cancellation.cancel();
+            task.await.unwrap();
+        }
+        let result = node.leave().await;
+        self.registry.unregister(id);
+        info!("Removed node {}", id);
+        Ok(result?)
+    }
+
+    async fn key_count(&self) -> Result<usize, NodeError> {
+        let mut count = 0;
+        for node in self.list_nodes() {
+            count += node.key_count().await?;
+        }
+        Ok(count)
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum NodeManagerError {
+    #[error("node {0} is already hosted by this process")]
+    AlreadyExists(ChordId),
+    #[error("node {0} is not hosted by this process")]
+    NotFound(ChordId),
+    #[error(transparent)]
+    NodeError(#[from] NodeError),
+}
+
+impl NodeManagerError {
+    pub fn already_exists(id: ChordId) -> Self {
+        NodeManagerError::AlreadyExists(id)
+    }
+
+    pub fn not_found(id: ChordId) -> Self {
+        NodeManagerError::NotFound(id)
+    }
+
+    pub fn get_code(&self) -> Code {
+        match self {
+            NodeManagerError::AlreadyExists(_) => Code::AlreadyExists,
+            NodeManagerError::NotFound(_) => Code::NotFound,
+            NodeManagerError::NodeError(node_error) => node_error.get_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::testing::TestNetwork;
+
+    use super::*;
+
+    async fn stabilize(nodes: &[Arc<DynLocalNode>]) {
+        for _ in 0..2 {
+            for node in nodes {
+                node.stabilize().await.unwrap();
+            }
+        }
+    }
+
+    async fn successor(node: &DynLocalNode) -> ChordId {
+        node.get_successors().await.unwrap()[0].id
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_nodes() {
+        let network = TestNetwork::new();
+        let manager = network.create_node_manager(Config::default());
+        let ids: Vec<_> = [10u128, 20, 30].map(ChordId::from).into();
+        for &id in &ids {
+            manager.add_node(Some(id)).await.unwrap();
+            stabilize(&manager.list_nodes()).await;
+        }
+        assert!(matches!(
+            manager.add_node(Some(ids[1])).await,
+            Err(NodeManagerError::AlreadyExists(_))
+        ));
+        for (i, &id) in ids.iter().enumerate() {
+            let node = manager.get_node(id).unwrap();
+            assert_eq!(successor(&*node).await, ids[(i + 1) % ids.len()]);
+        }
+
+        manager.remove_node(ids[1]).await.unwrap();
+        assert_eq!(manager.list_nodes().len(), 2);
+        assert!(manager.get_node(ids[1]).is_none());
+        let first = manager.get_node(ids[0]).unwrap();
+        let last = manager.get_node(ids[2]).unwrap();
+        assert_eq!(successor(&*first).await, ids[2]);
+        assert_eq!(last.get_predecessor().await.unwrap().unwrap().id, ids[0]);
+        assert!(matches!(
+            manager.remove_node(ids[1]).await,
+            Err(NodeManagerError::NotFound(_))
+        ));
     }
 }
@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use shaku::{Component, Interface};
+
+use chord_types::chord_id::ChordId;
+
+use crate::node::DynLocalNode;
+
+/// The local nodes hosted by this process.
+///
+/// The [`NodeManager`](crate::node_manager::NodeManager) keeps it up to date. It is a component of
+/// its own so that node clients can look up local nodes without depending on the node manager,
+/// which itself depends on them.
+pub trait NodeRegistry: Interface {
+    fn get_node(&self, id: ChordId) -> Option<Arc<DynLocalNode>>;
+
+    fn list_nodes(&self) -> Vec<Arc<DynLocalNode>>;
+
+    /// Returns `false` if a node with the same id is already registered, leaving it in place.
+    fn register(&self, node: Arc<DynLocalNode>) -> bool;
+
+    fn unregister(&self, id: ChordId) -> Option<Arc<DynLocalNode>>;
+}
+
+#[derive(Component)]
+#[shaku(interface = NodeRegistry)]
+pub struct DefaultNodeRegistry {
+    #[shaku(default)]
+    nodes: RwLock<HashMap<ChordId, Arc<DynLocalNode>>>,
+}
+
+impl NodeRegistry for DefaultNodeRegistry {
+    fn get_node(&self, id: ChordId) -> Option<Arc<DynLocalNode>> {
+        self.nodes.read().unwrap().get(&id).cloned()
+    }
+
+    fn list_nodes(&self) -> Vec<Arc<DynLocalNode>> {
+        self.nodes.read().unwrap().values().cloned().collect()
+    }
+
+    fn register(&self, node: Arc<DynLocalNode>) -> bool {
+        let mut nodes = self.nodes.write().unwrap();
+        if nodes.contains_key(&node.id()) {
+            return false;
+        }
+        nodes.insert(node.id(), node);
+        true
+    }
+
+    fn unregister(&self, id: ChordId) -> Option<Arc<DynLocalNode>> {
+        self.nodes.write().unwrap().remove(&id)
+    }
+}
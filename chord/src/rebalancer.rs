@@ -0,0 +1,580 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::{info, warn};
+use shaku::{Component, Interface};
+use thiserror::Error;
+use tokio::select;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tonic::Code;
+
+use chord_types::chord_id::ChordId;
+use chord_types::node_info::NodeInfo;
+use chord_types::ring_interval::RingInterval;
+
+use crate::config::{ConfigProvider, RebalanceConfig};
+use crate::node::{DynNode, NodeError};
+use crate::node_client_factory::NodeClientFactory;
+use crate::node_manager::{NodeManager, NodeManagerError};
+
+/// The maximum number of nodes the ring is walked for, in case it does not close.
+const MAX_RING_SIZE: usize = 1 << 16;
+
+#[async_trait]
+pub trait Rebalancer: Interface {
+    /// Measures which share of the ring each process owns, by walking the ring from a local node,
+    /// and asks each process how many keys it owns.
+    async fn report(&self) -> Result<OwnershipReport, RebalanceError>;
+
+    /// Adds, removes or moves virtual nodes of this process, one at a time, until it owns its
+    /// target share of the ring within the configured tolerance.
+    ///
+    /// Gives up after the configured maximum number of changes.
+    async fn rebalance(&self) -> Result<RebalanceOutcome, RebalanceError>;
+}
+
+/// The share of the ring owned by the nodes of each process.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct OwnershipReport {
+    pub processes: Vec<ProcessOwnership>,
+}
+
+impl OwnershipReport {
+    pub fn local(&self) -> Option<&ProcessOwnership> {
+        self.processes.iter().find(|process| process.local)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProcessOwnership {
+    /// The preferred endpoint of the nodes of the process, which identifies the process.
+    pub endpoint: String,
+    pub local: bool,
+    pub node_count: usize,
+    pub capacity: f64,
+    /// The owned fraction of the ring.
+    pub share: f64,
+    /// The fraction of the ring the process should own, according to its capacity.
+    pub target_share: f64,
+    /// The number of keys the nodes of the process own, if the process could be asked.
+    pub key_count: Option<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RebalanceOutcome {
+    pub before: OwnershipReport,
+    pub after: OwnershipReport,
+}
+
+/// A single change to the virtual nodes of this process.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Change {
+    Add(ChordId),
+    Remove(ChordId),
+    Move { from: ChordId, to: ChordId },
+}
+
+/// The nodes of the ring in ascending order of their ids, with the arc each of them owns.
+struct RingSnapshot {
+    nodes: Vec<OwnedArc>,
+    local_process: String,
+}
+
+struct OwnedArc {
+    node: NodeInfo,
+    process: String,
+    /// The id of the predecessor, which the owned arc starts after.
+    start: ChordId,
+    /// The owned fraction of the ring.
+    share: f64,
+}
+
+#[derive(Component)]
+#[shaku(interface = Rebalancer)]
+pub struct DefaultRebalancer {
+    #[shaku(inject)]
+    node_manager: Arc<dyn NodeManager>,
+
+    #[shaku(inject)]
+    client_factory: Arc<dyn NodeClientFactory>,
+
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+}
+
+impl DefaultRebalancer {
+    /// Collects the nodes of the ring by following successor lists from `start` until they lead
+    /// back to it.
+    async fn walk_ring(&self, start: Arc<DynNode>) -> Result<Vec<NodeInfo>, RebalanceError> {
+        let start_id = start.id();
+        let mut ring = vec![start.node_info()];
+        let mut current = start;
+        loop {
+            let successors = current.get_successors().await?;
+            let mut progressed = false;
+            for successor in successors {
+                if successor.id == start_id {
+                    return Ok(ring);
+                }
+                let last = ring.last().unwrap().id;
+                if RingInterval::open(last, start_id).contains(successor.id) {
+                    ring.push(successor);
+                    progressed = true;
+                }
+            }
+            if !progressed {
+                if ring.len() == 1 {
+                    // The start node does not know of any other nodes
+                    return Ok(ring);
+                }
+                return Err(RebalanceError::incomplete_ring(current.id()));
+            }
+            if ring.len() > MAX_RING_SIZE {
+                return Err(RebalanceError::incomplete_ring(current.id()));
+            }
+            current = self.client_factory.create_node_client(ring.last().unwrap());
+        }
+    }
+
+    async fn snapshot(&self) -> Result<RingSnapshot, RebalanceError> {
+        let start = self
+            .node_manager
+            .list_nodes()
+            .into_iter()
+            .min_by_key(|node| node.id())
+            .ok_or(RebalanceError::no_local_nodes())?;
+        let local_process = start.node_info().process();
+        let mut ring = self.walk_ring(start).await?;
+        ring.sort_by_key(|node| node.id);
+        Ok(RingSnapshot::new(ring, local_process))
+    }
+
+    async fn report_for(&self, snapshot: &RingSnapshot) -> Result<OwnershipReport, RebalanceError> {
+        let config = self.config_provider.get_config();
+        let mut processes: HashMap<&str, ProcessOwnership> = HashMap::new();
+        for arc in &snapshot.nodes {
+            let process = processes
+                .entry(&arc.process)
+                .or_insert_with(|| ProcessOwnership {
+                    endpoint: arc.process.clone(),
+                    local: arc.process == snapshot.local_process,
+                    node_count: 0,
+                    capacity: config.rebalance_config.capacity,
+                    share: 0.0,
+                    target_share: 0.0,
+                    key_count: None,
+                });
+            process.node_count += 1;
+            process.share += arc.share;
+        }
+        for process in processes.values_mut() {
+            if process.local {
+                process.key_count = Some(self.node_manager.key_count().await?);
+                continue;
+            }
+            let node = snapshot
+                .nodes
+                .iter()
+                .find(|arc| arc.process == process.endpoint)
+                .map(|arc| &arc.node);
+            let hosted = match node.and_then(NodeInfo::socket_address) {
+                Some(address) => match self.client_factory.get_hosted_nodes(address).await {
+                    Ok(hosted) => Some(hosted),
+                    Err(e) => {
+                        warn!("Failed to get the capacity of {}: {}", process.endpoint, e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            process.capacity = hosted
+                .as_ref()
+                .and_then(|hosted| hosted.capacity)
+                .unwrap_or(RebalanceConfig::default().capacity);
+            process.key_count = hosted.map(|hosted| hosted.key_count);
+        }
+        let total_capacity: f64 = processes.values().map(|process| process.capacity).sum();
+        let mut processes: Vec<_> = processes.into_values().collect();
+        for process in &mut processes {
+            process.target_share = process.capacity / total_capacity;
+        }
+        processes.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        Ok(OwnershipReport { processes })
+    }
+
+    async fn apply(&self, change: Change) -> Result<(), RebalanceError> {
+        info!("Rebalancing: {:?}", change);
+        match change {
+            Change::Add(id) => {
+                self.node_manager.add_node(Some(id)).await?;
+            }
+            Change::Remove(id) => self.node_manager.remove_node(id).await?,
+            Change::Move { from, to } => {
+                self.node_manager.add_node(Some(to)).await?;
+                self.node_manager.remove_node(from).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Rebalancer for DefaultRebalancer {
+    async fn report(&self) -> Result<OwnershipReport, RebalanceError> {
+        let snapshot = self.snapshot().await?;
+        self.report_for(&snapshot).await
+    }
+
+    async fn rebalance(&self) -> Result<RebalanceOutcome, RebalanceError> {
+        let config = self.config_provider.get_config().rebalance_config.clone();
+        let mut before = None;
+        for _ in 0..config.max_rounds {
+            let snapshot = self.snapshot().await?;
+            let report = self.report_for(&snapshot).await?;
+            let change = snapshot.plan(&report, config.tolerance);
+            before.get_or_insert(report);
+            let Some(change) = change else {
+                break;
+            };
+            self.apply(change).await?;
+            sleep(config.settle_time).await;
+        }
+        let after = self.report().await?;
+        Ok(RebalanceOutcome {
+            before: before.unwrap_or_else(|| after.clone()),
+            after,
+        })
+    }
+}
+
+impl RingSnapshot {
+    /// Expects the nodes in ascending order of their ids.
+    fn new(nodes: Vec<NodeInfo>, local_process: String) -> Self {
+        let predecessors: Vec<_> = nodes
+            .iter()
+            .cycle()
+            .skip(nodes.len() - 1)
+            .map(|node| node.id)
+            .take(nodes.len())
+            .collect();
+        let single = nodes.len() == 1;
+        let nodes = nodes
+            .into_iter()
+            .zip(predecessors)
+            .map(|(node, start)| OwnedArc {
+                process: node.process(),
+                share: if single {
+                    1.0
+                } else {
+                    start.distance_to(node.id).as_fraction()
+                },
+                start,
+                node,
+            })
+            .collect();
+        Self {
+            nodes,
+            local_process,
+        }
+    }
+
+    fn is_local(&self, arc: &OwnedArc) -> bool {
+        arc.process == self.local_process
+    }
+
+    /// Determines the change that brings this process closest to its target share, if it is not
+    /// within the tolerance already.
+    ///
+    /// A new node takes a part of the largest arc of a process that owns more than its target
+    /// share, or of any other process if there is none. A node gives away a part of its arc by
+    /// moving closer to its predecessor, which hands the part over to its successor. This is only
+    /// done with nodes whose successor belongs to another process.
+    ///
+    /// The key counts of the processes are not taken into account, since hashing distributes keys
+    /// evenly across the ring, so that the owned shares of the ring determine the key counts.
+    fn plan(&self, report: &OwnershipReport, tolerance: f64) -> Option<Change> {
+        let local = report.local()?;
+        let (share, target) = (local.share, local.target_share);
+        if share < target * (1.0 - tolerance) {
+            let deficit = target - share;
+            let over_target: Vec<&str> = report
+                .processes
+                .iter()
+                .filter(|process| process.share > process.target_share)
+                .map(|process| process.endpoint.as_str())
+                .collect();
+            let remote = || self.nodes.iter().filter(|arc| !self.is_local(arc));
+            let donor = remote()
+                .filter(|arc| over_target.contains(&arc.process.as_str()))
+                .max_by(|a, b| a.share.total_cmp(&b.share))
+                .or_else(|| remote().max_by(|a, b| a.share.total_cmp(&b.share)))?;
+            let id = donor.start + ChordId::from_fraction(deficit.min(donor.share / 2.0));
+            (id != donor.start).then_some(Change::Add(id))
+        } else if share > target * (1.0 + tolerance) {
+            let surplus = share - target;
+            let local_count = self.nodes.iter().filter(|arc| self.is_local(arc)).count();
+            let count = self.nodes.len();
+            let giver = (0..count)
+                .filter(|&i| self.is_local(&self.nodes[i]))
+                .filter(|&i| !self.is_local(&self.nodes[(i + 1) % count]))
+                .map(|i| &self.nodes[i])
+                .max_by(|a, b| a.share.total_cmp(&b.share))?;
+            let to = giver.start + ChordId::from_fraction(giver.share - surplus);
+            if giver.share > surplus && to != giver.start {
+                Some(Change::Move {
+                    from: giver.node.id,
+                    to,
+                })
+            } else if local_count > 1 {
+                Some(Change::Remove(giver.node.id))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// Spawns a task that rebalances every `interval`, until `cancellation` is triggered.
+pub fn start_rebalancing(
+    rebalancer: Arc<dyn Rebalancer>,
+    interval: Duration,
+    cancellation: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = cancellation.cancelled() => break,
+                _ = sleep(interval) => {}
+            }
+            match rebalancer.rebalance().await {
+                Ok(outcome) => {
+                    if let (Some(before), Some(after)) =
+                        (outcome.before.local(), outcome.after.local())
+                    {
+                        info!(
+                            "Rebalanced from {:.2}% to {:.2}% of the ring, target {:.2}%",
+                            before.share * 100.0,
+                            after.share * 100.0,
+                            after.target_share * 100.0,
+                        );
+                    }
+                }
+                Err(e) => warn!("Failed to rebalance: {}", e),
+            }
+        }
+    })
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum RebalanceError {
+    #[error("this process does not host any nodes")]
+    NoLocalNodes,
+    #[error("the ring could not be followed past node {0}")]
+    IncompleteRing(ChordId),
+    #[error(transparent)]
+    NodeError(#[from] NodeError),
+    #[error(transparent)]
+    NodeManagerError(#[from] NodeManagerError),
+}
+
+impl RebalanceError {
+    pub fn no_local_nodes() -> Self {
+        RebalanceError::NoLocalNodes
+    }
+
+    pub fn incomplete_ring(id: ChordId) -> Self {
+        RebalanceError::IncompleteRing(id)
+    }
+
+    pub fn get_code(&self) -> Code {
+        match self {
+            RebalanceError::NoLocalNodes => Code::FailedPrecondition,
+            RebalanceError::IncompleteRing(_) => Code::Unavailable,
+            RebalanceError::NodeError(node_error) => node_error.get_code(),
+            RebalanceError::NodeManagerError(node_manager_error) => node_manager_error.get_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use shaku::HasComponent;
+
+    use crate::config::Config;
+    use crate::key_value_client::KeyValueClient;
+    use crate::testing::TestNetwork;
+
+    use super::*;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+    }
+
+    fn node(fraction: f64, port: u16) -> NodeInfo {
+        NodeInfo::with_address(ChordId::from_fraction(fraction), address(port))
+    }
+
+    fn ownership(snapshot: &RingSnapshot, capacities: &[(u16, f64)]) -> OwnershipReport {
+        let total: f64 = capacities.iter().map(|&(_, capacity)| capacity).sum();
+        let processes = capacities
+            .iter()
+            .map(|&(port, capacity)| {
+                let endpoint = NodeInfo::with_address(ChordId::ZERO, address(port)).process();
+                ProcessOwnership {
+                    local: endpoint == snapshot.local_process,
+                    node_count: 0,
+                    capacity,
+                    share: snapshot
+                        .nodes
+                        .iter()
+                        .filter(|arc| arc.process == endpoint)
+                        .map(|arc| arc.share)
+                        .sum(),
+                    target_share: capacity / total,
+                    key_count: None,
+                    endpoint,
+                }
+            })
+            .collect();
+        OwnershipReport { processes }
+    }
+
+    #[test]
+    fn test_plan() {
+        let ring = vec![node(0.125, 1), node(0.5, 2)];
+        let first = RingSnapshot::new(ring.clone(), ring[0].process());
+        let second = RingSnapshot::new(ring.clone(), ring[1].process());
+        assert_eq!(first.nodes[0].share, 0.625);
+        assert_eq!(second.nodes[1].share, 0.375);
+
+        // The first node gives its surplus to its successor by moving backwards
+        let report = ownership(&first, &[(1, 1.0), (2, 1.0)]);
+        assert_eq!(
+            first.plan(&report, 0.1),
+            Some(Change::Move {
+                from: ring[0].id,
+                to: ChordId::ZERO
+            })
+        );
+        // The second process takes its deficit from the first
+        let report = ownership(&second, &[(1, 1.0), (2, 1.0)]);
+        assert_eq!(
+            second.plan(&report, 0.1),
+            Some(Change::Add(ChordId::from_fraction(0.625)))
+        );
+        let report = ownership(&second, &[(1, 1.6), (2, 1.0)]);
+        assert_eq!(second.plan(&report, 0.1), None);
+
+        // Only the second node has a successor of another process
+        let ring = vec![node(0.125, 1), node(0.25, 1), node(0.5, 2)];
+        let snapshot = RingSnapshot::new(ring.clone(), ring[0].process());
+        let report = ownership(&snapshot, &[(1, 11.0), (2, 5.0)]);
+        assert_eq!(
+            snapshot.plan(&report, 0.01),
+            Some(Change::Move {
+                from: ring[1].id,
+                to: ChordId::from_fraction(0.1875)
+            })
+        );
+        // It is removed if all of its arc is surplus
+        let report = ownership(&snapshot, &[(1, 1.0), (2, 1.0)]);
+        assert_eq!(snapshot.plan(&report, 0.01), Some(Change::Remove(ring[1].id)));
+    }
+
+    async fn stabilize(node_managers: &[Arc<dyn NodeManager>]) {
+        for _ in 0..4 {
+            for node_manager in node_managers {
+                for node in node_manager.list_nodes() {
+                    node.stabilize().await.unwrap();
+                }
+            }
+        }
+    }
+
+    fn key_counts(report: &OwnershipReport) -> Vec<usize> {
+        report
+            .processes
+            .iter()
+            .map(|process| process.key_count.unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_rebalance() {
+        let network = TestNetwork::new();
+        // Without maintenance, so that the ring only changes when it is stabilized below
+        let config = |port: u16, capacity: f64, join_addresses: Vec<SocketAddr>| Config {
+            advertised_address: Some(address(port)),
+            join_addresses,
+            rebalance_config: RebalanceConfig {
+                capacity,
+                tolerance: 0.05,
+                max_rounds: 1,
+                settle_time: Duration::ZERO,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut node_managers: Vec<Arc<dyn NodeManager>> = Vec::new();
+        let mut rebalancers: Vec<Arc<dyn Rebalancer>> = Vec::new();
+        for (port, capacity, fraction) in [(1, 1.0, 0.0), (2, 3.0, 0.5)] {
+            let join_addresses = if node_managers.is_empty() {
+                vec![]
+            } else {
+                vec![address(1)]
+            };
+            let program = network.create_process(config(port, capacity, join_addresses));
+            let node_manager: Arc<dyn NodeManager> = program.resolve();
+            node_manager
+                .add_node(Some(ChordId::from_fraction(fraction)))
+                .await
+                .unwrap();
+            node_managers.push(node_manager);
+            rebalancers.push(program.resolve());
+            stabilize(&node_managers).await;
+        }
+        let client: Arc<dyn KeyValueClient> = network
+            .create_process(config(3, 1.0, vec![address(1)]))
+            .resolve();
+        let start = node_managers[0].list_nodes().remove(0);
+        for i in 0..40 {
+            let key = format!("key-{}", i).into_bytes();
+            client.put(&*start, &key, key.clone(), None).await.unwrap();
+        }
+
+        // The second process adds a node, which takes over keys once the ring is stabilized
+        let outcome = rebalancers[1].rebalance().await.unwrap();
+        let before = outcome.before.local().unwrap();
+        assert_eq!((before.share, before.target_share), (0.5, 0.75));
+        assert_eq!(key_counts(&outcome.before).iter().sum::<usize>(), 40);
+        stabilize(&node_managers).await;
+
+        let outcome = rebalancers[1].rebalance().await.unwrap();
+        // Nothing is left to change
+        assert_eq!(outcome.before, outcome.after);
+        let after = outcome.after.local().unwrap();
+        assert_eq!(after.node_count, 2);
+        assert!((after.share - 0.75).abs() < 0.75 * 0.05);
+        let report = rebalancers[0].report().await.unwrap();
+        assert_eq!(report.processes.len(), 2);
+        assert!((report.local().unwrap().share - 0.25).abs() < 0.25 * 0.05);
+        assert_eq!(key_counts(&report), key_counts(&outcome.after));
+        assert_eq!(key_counts(&report).iter().sum::<usize>(), 40);
+    }
+}
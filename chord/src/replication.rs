@@ -0,0 +1,268 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chord_types::node_info::NodeInfo;
+
+use crate::config::Consistency;
+use crate::node::NodeError;
+use crate::storage::StorageError;
+
+/// A copy of an entry, sent by its owner to the nodes that replicate it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Replica {
+    pub key: Vec<u8>,
+    /// [None] if the key was deleted.
+    pub value: Option<Vec<u8>>,
+    /// The version of the write that produced this copy. Of two copies of a key, the one with the
+    /// higher version is newer.
+    pub version: u64,
+}
+
+impl Replica {
+    pub fn put(key: Vec<u8>, value: Vec<u8>, version: u64) -> Self {
+        Self {
+            key,
+            value: Some(value),
+            version,
+        }
+    }
+
+    pub fn delete(key: Vec<u8>, version: u64) -> Self {
+        Self {
+            key,
+            value: None,
+            version,
+        }
+    }
+
+    /// Encodes the version and value as they are stored by a node: the version as a 64-bit little
+    /// endian integer, followed by `1` and the value, or `0` for a deleted key.
+    pub fn encode_stored(&self) -> Vec<u8> {
+        let mut bytes = self.version.to_le_bytes().to_vec();
+        match &self.value {
+            Some(value) => {
+                bytes.push(1);
+                bytes.extend(value);
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    pub fn decode_stored(key: Vec<u8>, bytes: &[u8]) -> Result<Self, StorageError> {
+        let value = match bytes.split_first_chunk() {
+            Some((version, [1, value @ ..])) => {
+                Replica::put(key, value.to_vec(), 0).with_version(version)
+            }
+            Some((version, [0])) => Replica::delete(key, 0).with_version(version),
+            _ => return Err(StorageError::undecodable(key)),
+        };
+        Ok(value)
+    }
+
+    fn with_version(mut self, version: &[u8; 8]) -> Self {
+        self.version = u64::from_le_bytes(*version);
+        self
+    }
+}
+
+/// The version of a write that replaces a copy with version `current`.
+///
+/// Versions are timestamps in microseconds, but always exceed the version they replace, so that a
+/// write is newer than the copy it replaces even if the clock of its owner lags behind.
+pub fn next_version(current: Option<u64>) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64);
+    match current {
+        Some(current) => now.max(current.saturating_add(1)),
+        None => now,
+    }
+}
+
+/// Picks the nodes that replicate the entries of `owner` from its successors, closest first.
+///
+/// Virtual nodes hosted by the same process fail together, so only the first node of each process
+/// is picked, and none of the process of `owner`.
+pub fn select_replicas(
+    owner: &NodeInfo,
+    successors: impl IntoIterator<Item = NodeInfo>,
+    count: usize,
+) -> Vec<NodeInfo> {
+    let mut processes = HashSet::from([owner.process()]);
+    successors
+        .into_iter()
+        .filter(|node| processes.insert(node.process()))
+        .take(count)
+        .collect()
+}
+
+/// The length of the successor list that is guaranteed to contain `replication_factor - 1` nodes
+/// of processes other than the owner's, if every process hosts `virtual_nodes` nodes.
+///
+/// In the worst case, the list starts with the other nodes of the owner's process, followed by all
+/// nodes of one process after another.
+pub fn required_neighbors(replication_factor: usize, virtual_nodes: usize) -> usize {
+    replication_factor.saturating_sub(1) * virtual_nodes
+}
+
+/// The number of nodes, including the owner, that have to take part in a request with the given
+/// level, if each key is stored by `replication_factor` nodes.
+pub fn required_nodes(
+    consistency: Consistency,
+    replication_factor: usize,
+) -> Result<usize, NodeError> {
+    let required = match consistency {
+        Consistency::One => 1,
+        Consistency::Quorum => replication_factor / 2 + 1,
+        Consistency::All => replication_factor,
+        Consistency::Count(count) => count,
+    };
+    if required == 0 || required > replication_factor {
+        return Err(NodeError::invalid_consistency(
+            consistency,
+            replication_factor,
+        ));
+    }
+    Ok(required)
+}
+
+/// Picks the newest of the copies the nodes responded with to a read, where [None] stands for a
+/// node that does not store the key at all.
+pub fn resolve_read(copies: Vec<Option<Replica>>) -> Option<Vec<u8>> {
+    copies
+        .into_iter()
+        .flatten()
+        .max_by_key(|copy| copy.version)
+        .and_then(|copy| copy.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use chord_types::chord_id::ChordId;
+
+    use super::*;
+
+    fn node(id: u128, port: u16) -> NodeInfo {
+        NodeInfo::with_address(
+            ChordId::from(id),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        )
+    }
+
+    #[test]
+    fn test_select_replicas_skips_siblings() {
+        let owner = node(1, 1);
+        let successors = vec![node(2, 1), node(3, 2), node(4, 2), node(5, 3), node(6, 4)];
+        assert_eq!(
+            select_replicas(&owner, successors.clone(), 2),
+            vec![node(3, 2), node(5, 3)]
+        );
+        assert!(select_replicas(&owner, successors.clone(), 0).is_empty());
+        assert_eq!(
+            select_replicas(&owner, successors[..3].to_vec(), 2),
+            vec![node(3, 2)]
+        );
+    }
+
+    #[test]
+    fn test_required_neighbors() {
+        assert_eq!(required_neighbors(1, 8), 0);
+        assert_eq!(required_neighbors(3, 1), 2);
+        // Two siblings of the owner, the three nodes of one process, and a node of another
+        assert_eq!(required_neighbors(3, 3), 6);
+        let owner = node(0, 1);
+        let successors = [
+            node(1, 1),
+            node(2, 1),
+            node(3, 2),
+            node(4, 2),
+            node(5, 2),
+            node(6, 3),
+            node(7, 3),
+            node(8, 3),
+        ];
+        let length = required_neighbors(3, 3);
+        assert_eq!(
+            select_replicas(&owner, successors[..length].to_vec(), 2),
+            vec![node(3, 2), node(6, 3)]
+        );
+        assert_eq!(
+            select_replicas(&owner, successors[..length - 1].to_vec(), 2).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_required_nodes() {
+        assert_eq!(required_nodes(Consistency::One, 3).unwrap(), 1);
+        assert_eq!(required_nodes(Consistency::Quorum, 3).unwrap(), 2);
+        assert_eq!(required_nodes(Consistency::Quorum, 4).unwrap(), 3);
+        assert_eq!(required_nodes(Consistency::All, 3).unwrap(), 3);
+        assert_eq!(required_nodes(Consistency::Count(2), 3).unwrap(), 2);
+        assert!(matches!(
+            required_nodes(Consistency::Count(4), 3),
+            Err(NodeError::InvalidConsistency { .. })
+        ));
+        assert!(required_nodes(Consistency::Count(0), 3).is_err());
+    }
+
+    #[test]
+    fn test_resolve_read() {
+        let a = Replica::put(b"key".to_vec(), b"a".to_vec(), 1);
+        let b = Replica::put(b"key".to_vec(), b"b".to_vec(), 2);
+        let deleted = Replica::delete(b"key".to_vec(), 3);
+        // The newest copy wins, even if most nodes hold an older one
+        assert_eq!(
+            resolve_read(vec![Some(a.clone()), Some(b.clone()), Some(a.clone())]),
+            Some(b"b".to_vec())
+        );
+        assert_eq!(
+            resolve_read(vec![None, Some(a.clone()), None]),
+            Some(b"a".to_vec())
+        );
+        assert_eq!(resolve_read(vec![Some(b.clone()), Some(deleted)]), None);
+        assert_eq!(resolve_read(vec![None, None]), None);
+        assert_eq!(resolve_read(vec![]), None);
+    }
+
+    #[test]
+    fn test_stored_encoding() {
+        for replica in [
+            Replica::put(b"key".to_vec(), b"value".to_vec(), 7),
+            Replica::put(b"key".to_vec(), Vec::new(), u64::MAX),
+            Replica::delete(b"key".to_vec(), 8),
+        ] {
+            let bytes = replica.encode_stored();
+            assert_eq!(
+                Replica::decode_stored(b"key".to_vec(), &bytes).unwrap(),
+                replica
+            );
+        }
+        for bytes in [
+            &[][..],
+            &[1, 0, 0],
+            &[0; 8],
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+        ] {
+            assert!(Replica::decode_stored(b"key".to_vec(), bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn test_next_version() {
+        let now = next_version(None);
+        assert!(next_version(Some(now)) > now);
+        assert_eq!(next_version(Some(u64::MAX - 1)), u64::MAX);
+    }
+}
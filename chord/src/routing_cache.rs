@@ -0,0 +1,183 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use shaku::{Component, Interface};
+
+use chord_types::chord_id::ChordId;
+use chord_types::finger_table::FingerTableEntry;
+use chord_types::node_info::NodeInfo;
+use chord_types::ring_interval::RingInterval;
+
+use crate::config::ConfigProvider;
+
+/// Knowledge about the ring that is shared by all virtual nodes of this process.
+///
+/// Nodes feed it with the peers they learn about, and consult it for additional next hops when
+/// routing a lookup. It is never used for successor and predecessor lists, which every node keeps
+/// on its own. If it is disabled in the config, it stays empty.
+pub trait RoutingCache: Interface {
+    /// Records that `node` is part of the ring.
+    fn learn(&self, node: &NodeInfo);
+
+    /// Forgets the node with the given id, e.g. because it left the ring or was found to be dead.
+    fn forget(&self, id: ChordId);
+
+    /// At most `limit` known nodes that lie strictly between `from` and `id`, ordered by how
+    /// closely they precede `id`.
+    fn preceding_nodes(&self, from: ChordId, id: ChordId, limit: usize) -> Vec<NodeInfo>;
+}
+
+#[derive(Component)]
+#[shaku(interface = RoutingCache)]
+pub struct DefaultRoutingCache {
+    #[shaku(inject)]
+    config_provider: Arc<dyn ConfigProvider>,
+
+    #[shaku(default)]
+    nodes: RwLock<BTreeMap<ChordId, FingerTableEntry>>,
+}
+
+impl DefaultRoutingCache {
+    fn is_enabled(&self) -> bool {
+        self.config_provider
+            .get_config()
+            .routing_cache_config
+            .enabled
+    }
+}
+
+impl RoutingCache for DefaultRoutingCache {
+    fn learn(&self, node: &NodeInfo) {
+        if !self.is_enabled() {
+            return;
+        }
+        let capacity = self
+            .config_provider
+            .get_config()
+            .routing_cache_config
+            .capacity;
+        let mut nodes = self.nodes.write().unwrap();
+        nodes.insert(node.id, FingerTableEntry::new(node.clone()));
+        if nodes.len() > capacity {
+            // Evict the node that was confirmed least recently
+            let oldest = nodes
+                .values()
+                .min_by_key(|entry| entry.updated_at)
+                .map(|entry| entry.node_info.id);
+            if let Some(oldest) = oldest {
+                nodes.remove(&oldest);
+            }
+        }
+    }
+
+    fn forget(&self, id: ChordId) {
+        self.nodes.write().unwrap().remove(&id);
+    }
+
+    fn preceding_nodes(&self, from: ChordId, id: ChordId, limit: usize) -> Vec<NodeInfo> {
+        let interval = RingInterval::open(from, id);
+        let nodes = self.nodes.read().unwrap();
+        // Walk backwards from `id`, wrapping around the end of the ring
+        nodes
+            .range(..id)
+            .rev()
+            .chain(nodes.range(id..).rev())
+            .map(|(_, entry)| &entry.node_info)
+            .take_while(|node| interval.contains(node.id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use shaku::{module, HasComponent};
+
+    use crate::config::{
+        Config, DefaultConfigProvider, DefaultConfigProviderParameters, RoutingCacheConfig,
+    };
+
+    use super::*;
+
+    module! {
+        CacheProgram {
+            components = [DefaultConfigProvider, DefaultRoutingCache],
+            providers = []
+        }
+    }
+
+    fn create_cache(enabled: bool, capacity: usize) -> Arc<dyn RoutingCache> {
+        CacheProgram::builder()
+            .with_component_parameters::<DefaultConfigProvider>(DefaultConfigProviderParameters {
+                config: Arc::new(Config {
+                    routing_cache_config: RoutingCacheConfig { enabled, capacity },
+                    ..Default::default()
+                }),
+            })
+            .build()
+            .resolve()
+    }
+
+    fn node(id: u128) -> NodeInfo {
+        NodeInfo::with_address(
+            ChordId::from(id),
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 7000)),
+        )
+    }
+
+    fn ids(nodes: Vec<NodeInfo>) -> Vec<u128> {
+        nodes
+            .into_iter()
+            .map(|node| u128::from_be_bytes(node.id.to_be_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_preceding_nodes() {
+        let cache = create_cache(true, 16);
+        for id in [10, 20, 30, u128::MAX - 10] {
+            cache.learn(&node(id));
+        }
+        let (from, id) = (ChordId::from(15u128), ChordId::from(35u128));
+        assert_eq!(ids(cache.preceding_nodes(from, id, 16)), vec![30, 20]);
+        assert_eq!(ids(cache.preceding_nodes(from, id, 1)), vec![30]);
+        // The interval wraps around the end of the ring
+        let (from, id) = (ChordId::from(25u128), ChordId::from(15u128));
+        assert_eq!(
+            ids(cache.preceding_nodes(from, id, 16)),
+            vec![10, u128::MAX - 10, 30]
+        );
+        cache.forget(ChordId::from(10u128));
+        assert_eq!(
+            ids(cache.preceding_nodes(from, id, 16)),
+            vec![u128::MAX - 10, 30]
+        );
+    }
+
+    #[test]
+    fn test_capacity_and_disabled() {
+        let cache = create_cache(true, 2);
+        for id in [10, 20, 30] {
+            cache.learn(&node(id));
+        }
+        let all = cache.preceding_nodes(ChordId::ZERO, ChordId::MAX, 16);
+        assert_eq!(all.len(), 2);
+
+        let cache = create_cache(false, 2);
+        cache.learn(&node(10));
+        assert!(cache
+            .preceding_nodes(ChordId::ZERO, ChordId::MAX, 16)
+            .is_empty());
+    }
+}
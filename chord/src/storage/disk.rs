@@ -0,0 +1,589 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use log::warn;
+
+use crate::config::{FsyncPolicy, StorageConfig};
+use crate::storage::{Storage, StorageError};
+
+const LOG_FILE: &str = "wal.log";
+const SNAPSHOT_FILE: &str = "snapshot";
+const SNAPSHOT_TMP_FILE: &str = "snapshot.tmp";
+const LOCK_FILE: &str = "lock";
+
+/// Every record starts with the length and the CRC-32 checksum of its payload.
+const HEADER_LENGTH: usize = 8;
+
+const PUT: u8 = 0;
+const DELETE: u8 = 1;
+
+/// A [Storage] that keeps its entries in memory, and makes them durable in a directory.
+///
+/// Every mutation is appended to a write-ahead log before it is applied. Once the log holds
+/// [StorageConfig::snapshot_threshold] mutations, all entries are written to a snapshot, which
+/// atomically replaces the previous one, and the log is cleared.
+///
+/// On startup, the snapshot is loaded and the log is replayed on top of it. A log that ends in a
+/// partially written or corrupted record, e.g. after a crash, is truncated before that record.
+/// The directory is locked while the storage is open, so that no other storage writes to it.
+///
+/// All operations run on the blocking thread pool of the runtime, so that reads waiting for a
+/// mutation or snapshot to reach the disk do not stall the other requests of the process. With
+/// [FsyncPolicy::Batched], a background thread flushes mutations that waited for too long.
+pub struct DiskStorage {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    fsync_policy: FsyncPolicy,
+    snapshot_threshold: usize,
+    state: Mutex<State>,
+    /// Holds the lock on the directory until the storage is dropped.
+    _lock: File,
+}
+
+struct State {
+    entries: HashMap<Vec<u8>, Vec<u8>>,
+    log: File,
+    log_length: u64,
+    /// The number of mutations in the log.
+    logged: usize,
+    /// The number of mutations in the log that have not been flushed to disk yet.
+    pending: usize,
+    /// When the oldest of the pending mutations was written.
+    pending_since: Option<Instant>,
+}
+
+impl DiskStorage {
+    /// Opens the storage in `dir`, creating the directory if it does not exist, and recovers the
+    /// entries stored there.
+    pub fn open(dir: impl Into<PathBuf>, config: &StorageConfig) -> Result<Self, StorageError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let lock = File::create(dir.join(LOCK_FILE))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Err(StorageError::locked(dir)),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        let mut entries = HashMap::new();
+
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+        let snapshot = read_if_exists(&snapshot_path)?;
+        // The snapshot is replaced atomically, so it must never be damaged
+        if replay(&snapshot, &mut entries).1 != snapshot.len() {
+            return Err(StorageError::corrupted(snapshot_path));
+        }
+
+        let log_path = dir.join(LOG_FILE);
+        let log_data = read_if_exists(&log_path)?;
+        let (logged, valid) = replay(&log_data, &mut entries);
+        if valid != log_data.len() {
+            warn!(
+                "Truncating the write-ahead log {} from {} to {} bytes",
+                log_path.display(),
+                log_data.len(),
+                valid
+            );
+        }
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        log.set_len(valid as u64)?;
+        log.sync_all()?;
+
+        let storage = Self {
+            inner: Arc::new(Inner {
+                dir,
+                fsync_policy: config.fsync_policy,
+                snapshot_threshold: config.snapshot_threshold,
+                state: Mutex::new(State {
+                    entries,
+                    log,
+                    log_length: valid as u64,
+                    logged,
+                    pending: 0,
+                    pending_since: None,
+                }),
+                _lock: lock,
+            }),
+        };
+        if let FsyncPolicy::Batched { max_delay, .. } = config.fsync_policy {
+            let inner = Arc::downgrade(&storage.inner);
+            thread::Builder::new()
+                .name("wal-flush".to_string())
+                .spawn(move || flush_periodically(inner, max_delay))?;
+        }
+        Ok(storage)
+    }
+
+    /// Runs `f` on the blocking thread pool.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Inner) -> Result<T, StorageError> + Send + 'static,
+    ) -> Result<T, StorageError> {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || f(&inner))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+impl Inner {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        let mut state = self.state.lock().unwrap();
+        self.append(&mut state, &encode_record(PUT, &key, &value))?;
+        state.entries.insert(key, value);
+        self.snapshot_if_due(&mut state);
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<bool, StorageError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.contains_key(key) {
+            return Ok(false);
+        }
+        self.append(&mut state, &encode_record(DELETE, key, &[]))?;
+        state.entries.remove(key);
+        self.snapshot_if_due(&mut state);
+        Ok(true)
+    }
+
+    /// Appends a record to the log, and flushes it according to the fsync policy.
+    ///
+    /// If the record cannot be written or flushed, the log is cut back to its previous length, so
+    /// that a failed mutation is not replayed after a restart, and later records are not lost
+    /// behind a broken one.
+    fn append(&self, state: &mut State, record: &[u8]) -> Result<(), StorageError> {
+        if let Err(e) = state.log.write_all(record) {
+            let _ = state.log.set_len(state.log_length);
+            return Err(e.into());
+        }
+        let flush = match self.fsync_policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Batched {
+                max_pending,
+                max_delay,
+            } => {
+                state.pending + 1 >= max_pending
+                    || state
+                        .pending_since
+                        .is_some_and(|since| since.elapsed() >= max_delay)
+            }
+            FsyncPolicy::Never => false,
+        };
+        if flush {
+            if let Err(e) = state.log.sync_data() {
+                let _ = state.log.set_len(state.log_length);
+                return Err(e.into());
+            }
+        }
+        state.log_length += record.len() as u64;
+        state.logged += 1;
+        if flush {
+            state.pending = 0;
+            state.pending_since = None;
+        } else {
+            state.pending += 1;
+            state.pending_since.get_or_insert_with(Instant::now);
+        }
+        Ok(())
+    }
+
+    fn flush(&self, state: &mut State) -> io::Result<()> {
+        state.log.sync_data()?;
+        state.pending = 0;
+        state.pending_since = None;
+        Ok(())
+    }
+
+    /// Flushes the log if the oldest pending mutation waited for `max_delay`, and returns how long
+    /// to wait before checking again.
+    fn flush_if_stale(&self, max_delay: Duration) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let Some(since) = state.pending_since else {
+            return max_delay;
+        };
+        let waited = since.elapsed();
+        if waited < max_delay {
+            return max_delay - waited;
+        }
+        if let Err(e) = self.flush(&mut state) {
+            warn!("Failed to flush the write-ahead log: {}", e);
+        }
+        max_delay
+    }
+
+    /// Writes all entries to a new snapshot and clears the log, once the log is long enough.
+    ///
+    /// The mutation that made the snapshot due is durable in the log already, so a failed snapshot
+    /// is only logged, and retried after the next mutation.
+    fn snapshot_if_due(&self, state: &mut State) {
+        if state.logged < self.snapshot_threshold {
+            return;
+        }
+        if let Err(e) = self.snapshot(state) {
+            warn!("Failed to write a snapshot to {}: {}", self.dir.display(), e);
+        }
+    }
+
+    fn snapshot(&self, state: &mut State) -> Result<(), StorageError> {
+        let mut data = Vec::new();
+        for (key, value) in &state.entries {
+            data.extend(encode_record(PUT, key, value));
+        }
+        let tmp_path = self.dir.join(SNAPSHOT_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+        sync_dir(&self.dir)?;
+        // Replaying the old log on top of the new snapshot yields the same entries, so a crash
+        // before the log is cleared does no harm
+        state.log.set_len(0)?;
+        state.log.sync_all()?;
+        state.log_length = 0;
+        state.logged = 0;
+        state.pending = 0;
+        state.pending_since = None;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for DiskStorage {
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        let key = key.to_vec();
+        self.blocking(move |inner| Ok(inner.state.lock().unwrap().entries.get(&key).cloned()))
+            .await
+    }
+
+    async fn put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        self.blocking(move |inner| inner.put(key, value)).await
+    }
+
+    async fn delete(&self, key: &[u8]) -> Result<bool, StorageError> {
+        let key = key.to_vec();
+        self.blocking(move |inner| inner.delete(&key)).await
+    }
+
+    async fn entries(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        self.blocking(|inner| {
+            let state = inner.state.lock().unwrap();
+            Ok(state
+                .entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect())
+        })
+        .await
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            if state.pending > 0 {
+                if let Err(e) = state.log.sync_data() {
+                    warn!("Failed to flush the write-ahead log: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Flushes mutations that waited for `max_delay`, until the storage is dropped.
+fn flush_periodically(inner: Weak<Inner>, max_delay: Duration) {
+    let mut wait = max_delay;
+    loop {
+        thread::sleep(wait);
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        wait = inner.flush_if_stale(max_delay);
+    }
+}
+
+/// Encodes a mutation as `length, checksum, operation, key length, key, value`, with all numbers
+/// as 32-bit little endian integers.
+fn encode_record(operation: u8, key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(5 + key.len() + value.len());
+    payload.push(operation);
+    payload.extend((key.len() as u32).to_le_bytes());
+    payload.extend(key);
+    payload.extend(value);
+    let mut record = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    record.extend((payload.len() as u32).to_le_bytes());
+    record.extend(crc32fast::hash(&payload).to_le_bytes());
+    record.extend(payload);
+    record
+}
+
+/// Applies the records in `data` to `entries`, until the end of `data` or the first record that
+/// is incomplete, does not match its checksum or cannot be decoded.
+///
+/// Returns the number of applied records and the length of the valid prefix of `data`.
+fn replay(data: &[u8], entries: &mut HashMap<Vec<u8>, Vec<u8>>) -> (usize, usize) {
+    let mut count = 0;
+    let mut offset = 0;
+    while let Some((operation, key, value, length)) = decode_record(&data[offset..]) {
+        match operation {
+            PUT => entries.insert(key.to_vec(), value.to_vec()),
+            _ => entries.remove(key),
+        };
+        count += 1;
+        offset += length;
+    }
+    (count, offset)
+}
+
+/// Decodes the record at the start of `data` into its operation, key, value and total length.
+fn decode_record(data: &[u8]) -> Option<(u8, &[u8], &[u8], usize)> {
+    let header = data.get(..HEADER_LENGTH)?;
+    let length = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    let payload = data.get(HEADER_LENGTH..HEADER_LENGTH.checked_add(length)?)?;
+    if crc32fast::hash(payload) != checksum {
+        return None;
+    }
+    let (&operation, rest) = payload.split_first()?;
+    let key_length = u32::from_le_bytes(rest.get(..4)?.try_into().unwrap()) as usize;
+    let key = rest.get(4..4usize.checked_add(key_length)?)?;
+    let value = &rest[4 + key_length..];
+    match operation {
+        PUT | DELETE => Some((operation, key, value, HEADER_LENGTH + length)),
+        _ => None,
+    }
+}
+
+fn read_if_exists(path: &Path) -> Result<Vec<u8>, StorageError> {
+    match fs::read(path) {
+        Ok(data) => Ok(data),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Makes a rename within `dir` durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const KEY_COUNT: usize = 10;
+
+    fn config(fsync_policy: FsyncPolicy, snapshot_threshold: usize) -> StorageConfig {
+        StorageConfig {
+            data_dir: None,
+            fsync_policy,
+            snapshot_threshold,
+        }
+    }
+
+    fn key(index: usize) -> Vec<u8> {
+        format!("key-{}", index).into_bytes()
+    }
+
+    /// Performs a random put or delete, and returns whether it changed anything.
+    async fn mutate(storage: &DiskStorage, rng: &mut StdRng) -> bool {
+        let key = key(rng.gen_range(0..KEY_COUNT));
+        if rng.gen_bool(0.25) {
+            storage.delete(&key).await.unwrap()
+        } else {
+            let value: Vec<u8> = (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect();
+            storage.put(key, value).await.unwrap();
+            true
+        }
+    }
+
+    async fn contents(storage: &DiskStorage) -> HashMap<Vec<u8>, Vec<u8>> {
+        let mut contents = HashMap::new();
+        for index in 0..KEY_COUNT {
+            if let Some(value) = storage.get(&key(index)).await.unwrap() {
+                contents.insert(key(index), value);
+            }
+        }
+        assert_eq!(storage.entries().await.unwrap().len(), contents.len());
+        contents
+    }
+
+    #[tokio::test]
+    async fn test_recovers_snapshot_and_log() {
+        let dir = tempdir().unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let expected = {
+            let storage = DiskStorage::open(dir.path(), &config(FsyncPolicy::Always, 16)).unwrap();
+            for _ in 0..100 {
+                mutate(&storage, &mut rng).await;
+            }
+            contents(&storage).await
+        };
+        assert!(dir.path().join(SNAPSHOT_FILE).exists());
+        let storage = DiskStorage::open(dir.path(), &config(FsyncPolicy::Always, 16)).unwrap();
+        assert_eq!(contents(&storage).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_recovers_from_truncated_log() {
+        let dir = tempdir().unwrap();
+        let mut rng = StdRng::seed_from_u64(2);
+        let config = config(FsyncPolicy::Never, usize::MAX);
+        // The log length and the contents after each mutation
+        let mut states = vec![(0, HashMap::new())];
+        {
+            let storage = DiskStorage::open(dir.path(), &config).unwrap();
+            for _ in 0..50 {
+                if mutate(&storage, &mut rng).await {
+                    let length = fs::metadata(dir.path().join(LOG_FILE)).unwrap().len();
+                    states.push((length, contents(&storage).await));
+                }
+            }
+        }
+        let log = fs::read(dir.path().join(LOG_FILE)).unwrap();
+
+        for _ in 0..20 {
+            let offset = rng.gen_range(0..=log.len());
+            let crashed = tempdir().unwrap();
+            fs::write(crashed.path().join(LOG_FILE), &log[..offset]).unwrap();
+            let (_, expected) = states
+                .iter()
+                .rev()
+                .find(|(length, _)| *length as usize <= offset)
+                .unwrap();
+
+            let storage = DiskStorage::open(crashed.path(), &config).unwrap();
+            assert_eq!(&contents(&storage).await, expected, "offset {}", offset);
+            // New mutations are not lost behind the torn record
+            storage.put(key(0), b"new".to_vec()).await.unwrap();
+            drop(storage);
+            let storage = DiskStorage::open(crashed.path(), &config).unwrap();
+            assert_eq!(storage.get(&key(0)).await.unwrap(), Some(b"new".to_vec()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stops_at_corrupted_record() {
+        let dir = tempdir().unwrap();
+        let config = config(
+            FsyncPolicy::Batched {
+                max_pending: 2,
+                max_delay: Duration::from_secs(60),
+            },
+            usize::MAX,
+        );
+        {
+            let storage = DiskStorage::open(dir.path(), &config).unwrap();
+            for index in 0..3 {
+                storage.put(key(index), b"value".to_vec()).await.unwrap();
+            }
+        }
+        let path = dir.path().join(LOG_FILE);
+        let mut log = fs::read(&path).unwrap();
+        // Flip a bit in the value of the second record
+        let record_length = log.len() / 3;
+        log[2 * record_length - 1] ^= 1;
+        fs::write(&path, &log).unwrap();
+
+        let storage = DiskStorage::open(dir.path(), &config).unwrap();
+        assert_eq!(storage.get(&key(0)).await.unwrap(), Some(b"value".to_vec()));
+        assert_eq!(storage.entries().await.unwrap().len(), 1);
+        assert_eq!(fs::metadata(&path).unwrap().len(), record_length as u64);
+    }
+
+    #[tokio::test]
+    async fn test_survives_failed_snapshot() {
+        let dir = tempdir().unwrap();
+        let config = config(FsyncPolicy::Always, 2);
+        // The temporary snapshot cannot be created where a directory is in the way
+        let tmp_path = dir.path().join(SNAPSHOT_TMP_FILE);
+        fs::create_dir(&tmp_path).unwrap();
+        {
+            let storage = DiskStorage::open(dir.path(), &config).unwrap();
+            for index in 0..3 {
+                storage.put(key(index), b"value".to_vec()).await.unwrap();
+            }
+            assert!(storage.delete(&key(0)).await.unwrap());
+        }
+        assert!(!dir.path().join(SNAPSHOT_FILE).exists());
+        let storage = DiskStorage::open(dir.path(), &config).unwrap();
+        assert_eq!(contents(&storage).await.len(), 2);
+
+        // The snapshot is retried with the next mutation
+        fs::remove_dir(&tmp_path).unwrap();
+        storage.put(key(0), b"value".to_vec()).await.unwrap();
+        assert!(dir.path().join(SNAPSHOT_FILE).exists());
+        assert_eq!(fs::metadata(dir.path().join(LOG_FILE)).unwrap().len(), 0);
+        drop(storage);
+        let storage = DiskStorage::open(dir.path(), &config).unwrap();
+        assert_eq!(contents(&storage).await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_batch_after_delay() {
+        let dir = tempdir().unwrap();
+        let config = config(
+            FsyncPolicy::Batched {
+                max_pending: usize::MAX,
+                max_delay: Duration::from_millis(20),
+            },
+            usize::MAX,
+        );
+        let storage = DiskStorage::open(dir.path(), &config).unwrap();
+        storage.put(key(0), b"value".to_vec()).await.unwrap();
+        assert_eq!(storage.inner.state.lock().unwrap().pending, 1);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(storage.inner.state.lock().unwrap().pending, 0);
+    }
+
+    #[tokio::test]
+    async fn test_locks_directory() {
+        let dir = tempdir().unwrap();
+        let config = config(FsyncPolicy::Always, 16);
+        let storage = DiskStorage::open(dir.path(), &config).unwrap();
+        assert!(matches!(
+            DiskStorage::open(dir.path(), &config),
+            Err(StorageError::Locked(_))
+        ));
+        drop(storage);
+        DiskStorage::open(dir.path(), &config).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_corrupted_snapshot() {
+        let dir = tempdir().unwrap();
+        let mut snapshot = encode_record(PUT, b"key", b"value");
+        snapshot.pop();
+        fs::write(dir.path().join(SNAPSHOT_FILE), snapshot).unwrap();
+        let result = DiskStorage::open(dir.path(), &config(FsyncPolicy::Always, 16));
+        assert!(matches!(result, Err(StorageError::Corrupted(_))));
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+
+use crate::storage::{Storage, StorageError};
+
+/// A [Storage] that keeps everything in memory, and loses it when the process exits.
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.entries.read().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        self.entries.write().unwrap().insert(key, value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &[u8]) -> Result<bool, StorageError> {
+        Ok(self.entries.write().unwrap().remove(key).is_some())
+    }
+
+    async fn entries(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        Ok(self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_storage() {
+        let storage = MemoryStorage::default();
+        assert_eq!(storage.get(b"foo").await.unwrap(), None);
+        storage.put(b"foo".to_vec(), b"1".to_vec()).await.unwrap();
+        storage.put(b"foo".to_vec(), b"2".to_vec()).await.unwrap();
+        assert_eq!(storage.get(b"foo").await.unwrap(), Some(b"2".to_vec()));
+        assert_eq!(storage.entries().await.unwrap().len(), 1);
+        assert_eq!(
+            storage.entries().await.unwrap(),
+            vec![(b"foo".to_vec(), b"2".to_vec())]
+        );
+        assert!(storage.delete(b"foo").await.unwrap());
+        assert!(!storage.delete(b"foo").await.unwrap());
+        assert_eq!(storage.get(b"foo").await.unwrap(), None);
+    }
+}
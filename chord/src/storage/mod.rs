@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tonic::Code;
+
+pub mod disk;
+pub mod memory;
+
+/// The key-value pairs a single node is responsible for.
+///
+/// Keys are the application keys, before they are hashed onto the ring. Nodes store the version of
+/// each entry along with its value, and keep deleted keys with their version, see
+/// [crate::replication::Replica::encode_stored].
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    async fn put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError>;
+
+    /// Removes `key`, returning whether it was present.
+    async fn delete(&self, key: &[u8]) -> Result<bool, StorageError>;
+
+    /// A copy of all stored key-value pairs, in no particular order.
+    async fn entries(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError>;
+}
+
+pub type BoxedStorage = Box<dyn Storage>;
+
+#[derive(Clone, Debug, Error)]
+pub enum StorageError {
+    #[error("i/o error: {0}")]
+    Io(Arc<io::Error>),
+    #[error("corrupted file: {}", .0.display())]
+    Corrupted(PathBuf),
+    #[error("undecodable value stored under key {0:?}")]
+    Undecodable(Vec<u8>),
+    #[error("directory {} is used by another storage", .0.display())]
+    Locked(PathBuf),
+}
+
+impl StorageError {
+    pub fn get_code(&self) -> Code {
+        match self {
+            StorageError::Io(_) => Code::Internal,
+            StorageError::Corrupted(_) | StorageError::Undecodable(_) => Code::DataLoss,
+            StorageError::Locked(_) => Code::FailedPrecondition,
+        }
+    }
+
+    pub fn corrupted(path: impl Into<PathBuf>) -> Self {
+        StorageError::Corrupted(path.into())
+    }
+
+    pub fn undecodable(key: Vec<u8>) -> Self {
+        StorageError::Undecodable(key)
+    }
+
+    pub fn locked(dir: impl Into<PathBuf>) -> Self {
+        StorageError::Locked(dir.into())
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(value: io::Error) -> Self {
+        StorageError::Io(Arc::new(value))
+    }
+}
@@ -0,0 +1,354 @@
+/*
+ * Copyright (c) 2024 Maximilian Barmetler <https://barmetler.com>
+ *
+ * Use of this source code is governed by an MIT-style
+ * license that can be found in the LICENSE file or at
+ * https://opensource.org/licenses/MIT.
+ */
+
+//! An in-memory network of local nodes, which replaces grpc and failure detection in tests.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use shaku::{module, HasComponent};
+use tonic::Status;
+
+use chord_types::chord_id::ChordId;
+use chord_types::node_info::NodeInfo;
+
+use crate::config::{Config, Consistency, DefaultConfigProvider, DefaultConfigProviderParameters};
+use crate::failure_detector::{FailureDetector, NodeStatus, PingFailureDetector};
+use crate::key_value_client::DefaultKeyValueClient;
+use crate::lookup_driver::DefaultLookupDriver;
+use crate::maintenance::DefaultMaintenance;
+use crate::membership::DefaultMembership;
+use crate::node::{
+    DynLocalNode, DynNode, FindSuccessorOutcome, FindSuccessorParameters, Node, NodeError,
+    RingParameters, TransferRangeParameters, TransferStream,
+};
+use crate::node_client_factory::{GrpcNodeClientFactory, HostedNodes, NodeClientFactory};
+use crate::node_factory::{DefaultNodeFactory, NodeFactory};
+use crate::node_manager::{NodeManager, NodeManagerImpl};
+use crate::node_registry::DefaultNodeRegistry;
+use crate::rebalancer::DefaultRebalancer;
+use crate::replication::Replica;
+use crate::routing_cache::DefaultRoutingCache;
+
+module! {
+    pub TestProgram {
+        components = [
+            DefaultConfigProvider,
+            DefaultKeyValueClient,
+            DefaultLookupDriver,
+            DefaultMaintenance,
+            DefaultMembership,
+            DefaultNodeFactory,
+            DefaultNodeRegistry,
+            DefaultRebalancer,
+            DefaultRoutingCache,
+            GrpcNodeClientFactory,
+            NodeManagerImpl,
+            PingFailureDetector,
+        ],
+        providers = []
+    }
+}
+
+/// The config of a process, and the node manager hosting its nodes.
+type TestProcess = (Arc<Config>, Arc<dyn NodeManager>);
+
+#[derive(Default)]
+pub struct TestNetwork {
+    nodes: RwLock<HashMap<ChordId, Arc<DynLocalNode>>>,
+    killed: RwLock<HashSet<ChordId>>,
+    /// Processes whose nodes are managed by their node manager, and are reachable through this
+    /// network as well.
+    processes: RwLock<Vec<TestProcess>>,
+}
+
+impl TestNetwork {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Creates a local node for each id, which reach each other through this network.
+    pub fn create_nodes(
+        self: &Arc<Self>,
+        ids: &[ChordId],
+        config: Config,
+    ) -> Vec<Arc<DynLocalNode>> {
+        let factory: Arc<dyn NodeFactory> = self.create_program(config).resolve();
+        let nodes: Vec<Arc<DynLocalNode>> = ids
+            .iter()
+            .map(|&id| Arc::from(factory.create_node(id).unwrap()))
+            .collect();
+        self.nodes
+            .write()
+            .unwrap()
+            .extend(nodes.iter().map(|node| (node.id(), node.clone())));
+        nodes
+    }
+
+    /// Creates a node manager, whose nodes reach each other and all other nodes through this
+    /// network.
+    pub fn create_node_manager(self: &Arc<Self>, config: Config) -> Arc<dyn NodeManager> {
+        self.create_process(config).resolve()
+    }
+
+    /// Creates the components of a process, whose node manager is registered with this network.
+    ///
+    /// The process is reachable under its advertised address.
+    pub fn create_process(self: &Arc<Self>, config: Config) -> TestProgram {
+        let config = Arc::new(config);
+        let program = self.create_program(config.clone());
+        self.processes
+            .write()
+            .unwrap()
+            .push((config, program.resolve()));
+        program
+    }
+
+    fn create_program(self: &Arc<Self>, config: impl Into<Arc<Config>>) -> TestProgram {
+        TestProgram::builder()
+            .with_component_parameters::<DefaultConfigProvider>(DefaultConfigProviderParameters {
+                config: config.into(),
+            })
+            .with_component_override::<dyn NodeClientFactory>(Box::new(TestNetworkHandle(
+                self.clone(),
+            )))
+            .with_component_override::<dyn FailureDetector>(Box::new(TestNetworkHandle(
+                self.clone(),
+            )))
+            .build()
+    }
+
+    /// Makes the node unreachable, as if its process had crashed.
+    pub fn kill(&self, id: ChordId) {
+        self.killed.write().unwrap().insert(id);
+    }
+
+    pub fn is_alive(&self, id: ChordId) -> bool {
+        !self.killed.read().unwrap().contains(&id)
+    }
+
+    fn get_node(&self, id: ChordId) -> Result<Arc<DynLocalNode>, NodeError> {
+        if !self.is_alive(id) {
+            return Err(NodeError::status_error(Status::unavailable("node is down")));
+        }
+        self.all_nodes()
+            .into_iter()
+            .find(|node| node.id() == id)
+            .ok_or(NodeError::status_error(Status::not_found("node not found")))
+    }
+
+    fn all_nodes(&self) -> Vec<Arc<DynLocalNode>> {
+        let mut nodes: Vec<_> = self.nodes.read().unwrap().values().cloned().collect();
+        for (_, node_manager) in self.processes.read().unwrap().iter() {
+            nodes.extend(node_manager.list_nodes());
+        }
+        nodes
+    }
+}
+
+struct TestNetworkHandle(Arc<TestNetwork>);
+
+#[async_trait]
+impl NodeClientFactory for TestNetworkHandle {
+    fn create_node_client(&self, node_info: &NodeInfo) -> Arc<DynNode> {
+        Arc::new(TestNodeClient {
+            network: self.0.clone(),
+            node_info: node_info.clone(),
+        })
+    }
+
+    async fn get_hosted_nodes(&self, address: SocketAddr) -> Result<HostedNodes, NodeError> {
+        let process = self
+            .0
+            .processes
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(config, _)| config.advertised_address == Some(address))
+            .cloned();
+        // Nodes that do not belong to a process are all reachable under any address
+        let (nodes, capacity) = match process {
+            Some((config, node_manager)) => (
+                node_manager.list_nodes(),
+                Some(config.rebalance_config.capacity),
+            ),
+            None => (self.0.all_nodes(), None),
+        };
+        let nodes: Vec<_> = nodes
+            .into_iter()
+            .filter(|node| self.0.is_alive(node.id()))
+            .collect();
+        let mut key_count = 0;
+        for node in &nodes {
+            key_count += node.key_count().await?;
+        }
+        Ok(HostedNodes {
+            nodes: nodes.iter().map(|node| node.node_info()).collect(),
+            capacity,
+            key_count,
+        })
+    }
+}
+
+#[async_trait]
+impl FailureDetector for TestNetworkHandle {
+    async fn check_node(&self, node_info: &NodeInfo) -> NodeStatus {
+        if self.0.is_alive(node_info.id) {
+            NodeStatus::Alive
+        } else {
+            NodeStatus::Dead
+        }
+    }
+
+    fn report_left(&self, _node_info: &NodeInfo) {}
+}
+
+/// Calls the target node directly, unless it has been killed.
+struct TestNodeClient {
+    network: Arc<TestNetwork>,
+    node_info: NodeInfo,
+}
+
+#[async_trait]
+impl Node for TestNodeClient {
+    fn id(&self) -> ChordId {
+        self.node_info.id
+    }
+
+    fn node_info(&self) -> NodeInfo {
+        self.node_info.clone()
+    }
+
+    async fn find_successor(
+        &self,
+        parameters: FindSuccessorParameters,
+    ) -> Result<FindSuccessorOutcome, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .find_successor(parameters)
+            .await
+    }
+
+    async fn get_predecessor(&self) -> Result<Option<NodeInfo>, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .get_predecessor()
+            .await
+    }
+
+    async fn get_successors(&self) -> Result<Vec<NodeInfo>, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .get_successors()
+            .await
+    }
+
+    async fn get_predecessors(&self) -> Result<Vec<NodeInfo>, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .get_predecessors()
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), NodeError> {
+        self.network.get_node(self.node_info.id)?.ping().await
+    }
+
+    async fn get_ring_parameters(&self) -> Result<RingParameters, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .get_ring_parameters()
+            .await
+    }
+
+    async fn notify(&self, node: NodeInfo) -> Result<(), NodeError> {
+        self.network.get_node(self.node_info.id)?.notify(node).await
+    }
+
+    async fn successor_leaving(
+        &self,
+        leaving: NodeInfo,
+        successors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .successor_leaving(leaving, successors)
+            .await
+    }
+
+    async fn predecessor_leaving(
+        &self,
+        leaving: NodeInfo,
+        predecessors: Vec<NodeInfo>,
+    ) -> Result<(), NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .predecessor_leaving(leaving, predecessors)
+            .await
+    }
+
+    async fn get(
+        &self,
+        key: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<Option<Vec<u8>>, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .get(key, consistency)
+            .await
+    }
+
+    async fn put(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        consistency: Option<Consistency>,
+    ) -> Result<(), NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .put(key, value, consistency)
+            .await
+    }
+
+    async fn delete(&self, key: Vec<u8>) -> Result<bool, NodeError> {
+        self.network.get_node(self.node_info.id)?.delete(key).await
+    }
+
+    async fn get_replica(&self, key: Vec<u8>) -> Result<Option<Replica>, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .get_replica(key)
+            .await
+    }
+
+    async fn replicate(&self, replicas: Vec<Replica>) -> Result<(), NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .replicate(replicas)
+            .await
+    }
+
+    async fn drop_replicas(&self, start: ChordId, end: ChordId) -> Result<(), NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .drop_replicas(start, end)
+            .await
+    }
+
+    async fn transfer_range(
+        &self,
+        parameters: TransferRangeParameters,
+    ) -> Result<TransferStream, NodeError> {
+        self.network
+            .get_node(self.node_info.id)?
+            .transfer_range(parameters)
+            .await
+    }
+}
@@ -5,5 +5,4 @@
  * license that can be found in the LICENSE file or at
  * https://opensource.org/licenses/MIT.
  */
-pub mod looping_range;
 pub mod shutdown_source;